@@ -1,21 +1,22 @@
 use std::{
-    fs::{self, create_dir_all, File},
-    io::{Write, BufReader},
-    os::unix::prelude::OpenOptionsExt,
+    fs::create_dir_all,
+    io::BufReader,
     process::exit,
 };
 
 use camino::Utf8PathBuf;
 use data_encoding::HEXUPPER;
+use mcsync_proto::info::ServerInfo;
 use paris::{error, warn};
+use ring::signature::Ed25519KeyPair;
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use uuid::Uuid;
 
-use crate::utils::hash::sha256_digest;
+use crate::{migration::{self, CONFIG_STEPS}, utils::{file, hash::sha256_digest, signing}};
 
-pub const CONFIG_VERSION: u16 = 1;
-pub const SERVERINFO_VERSION: u16 = 1;
+pub const CONFIG_VERSION: u16 = mcsync_proto::migration::latest(CONFIG_STEPS);
 
 #[derive(Clone)]
 pub struct Config {
@@ -35,6 +36,9 @@ pub struct ClientConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientKeys {
     pub(crate) wg: String,
+
+    /// Signs our client info, so server owners can tell it really comes from us.
+    pub(crate) identity: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +52,9 @@ pub struct ClientServer {
     pub(crate) tool_subnet: String,
     pub(crate) ipv4_address: String,
     pub(crate) dns: String,
+
+    /// Key the server signed its server info with. Empty if it wasn't signed.
+    pub(crate) signing_key: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,120 +67,99 @@ pub struct ClientSync {
     pub(crate) share: bool,
 }
 
-// Copied over from server. Maybe I use a shared project or something.
-#[derive(Debug, Deserialize)]
-pub struct ServerInfo {
-    pub(crate) version: u16,
-    pub(crate) endpoint: String,
-    pub(crate) public_key: String,
-    pub(crate) psk: String,
-    pub(crate) tool_subnet: String,
-    pub(crate) user_subnet: String,
-    pub(crate) ipv4_address: String,
-    pub(crate) dns: String,
-}
-
 impl Config {
     pub fn new(config_path: Utf8PathBuf) -> Self {
+        if config_path.exists() && !config_path.is_file() {
+            error!("Selected config file is actually not a file.");
+            exit(1);
+        }
+
+        let mut migrated = None;
 
-        let data: ClientConfig = if config_path.exists() {
-            // Open already exixisting file.
-            match File::open(&config_path) {
-                Ok(file) => match serde_json::from_reader(file) {
-                    Ok(json) => json,
-                    Err(error) => {
-                        error!("Config file has syntax errors: {}", error);
-                        exit(1);
+        let (data, fingerprint) = match file::read_json::<Value>(config_path.as_std_path()) {
+            Ok(Some((value, contents))) => match migration::upgrade::<ClientConfig>(value, CONFIG_STEPS) {
+                Ok((data, None)) => (data, fingerprint(&contents)),
+                Ok((data, Some(version))) => {
+                    match file::keep_before_migration(config_path.as_std_path(), version) {
+                        Ok(backup) => migrated = Some((version, backup)),
+                        Err(error) => {
+                            error!("Couldn't keep a copy of {} before upgrading it: {}", &config_path, error);
+                            exit(1);
+                        }
                     }
+
+                    // The flush below writes the upgraded config.
+                    (data, String::new())
                 },
                 Err(error) => {
-                    error!("Couldn't open config file at {}: {}", &config_path, error);
+                    error!("Couldn't load config file at {}: {}", &config_path, error);
                     exit(1);
                 }
-            }
-        } else {
-            // Remove file from path.
-            let mut path_clone = config_path.clone();
-            path_clone.pop();
-
-            // If parent directory doesn't exist, try to create the path.
-            if !path_clone.exists() {
-                match create_dir_all(&path_clone) {
-                    Ok(_) => {}
-                    Err(error) => {
-                        error!(
-                            "Failed to recursivly create all directories to {}: {}",
-                            path_clone.to_string(),
-                            error
-                        );
-                        exit(1);
-                    }
-                }
-            }
-
-            // Create default config.
-            // Note that we assign chmod 660 to the new config file since it contains private keys.
-            let file_options = fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .mode(0o660)
-                .open(&config_path);
-            
-            match file_options {
-                Ok(mut file) => {
-                    // We now need to generate both WireGuard keys.
-                    let wg = wireguard_keys::Privkey::generate();
-
-                    let new_config = ClientConfig {
-                        version: 1,
-                        keys: ClientKeys { wg: wg.to_base64() },
-                        server: vec![],
-                        sync: vec![]
-                    };
-
-                    match serde_json::to_string_pretty(&new_config) {
-                        Ok(json) => match file.write_all(json.as_bytes()) {
-                            Ok(_) => {}
-                            Err(error) => {
-                                error!("Couldn't write new config at {}: {}", config_path, error);
-                                exit(1);
-                            }
-                        },
+            },
+            Ok(None) => {
+                // Remove file from path.
+                let mut path_clone = config_path.clone();
+                path_clone.pop();
+
+                // If parent directory doesn't exist, try to create the path.
+                if !path_clone.exists() {
+                    match create_dir_all(&path_clone) {
+                        Ok(_) => {}
                         Err(error) => {
-                            error!("Couldn't create JSON string: {}", error);
+                            error!(
+                                "Failed to recursivly create all directories to {}: {}",
+                                path_clone.to_string(),
+                                error
+                            );
                             exit(1);
                         }
                     }
-
-                    new_config
-                }
-                Err(error) => {
-                    error!("Couldn't create config file at {}: {}", &config_path, error);
-                    exit(1);
                 }
+
+                // We now need to generate both WireGuard keys. The config gets written by the flush below.
+                let wg = wireguard_keys::Privkey::generate();
+
+                let new_config = ClientConfig {
+                    version: CONFIG_VERSION,
+                    keys: ClientKeys { wg: wg.to_base64(), identity: signing::generate_identity() },
+                    server: vec![],
+                    sync: vec![]
+                };
+
+                (new_config, String::new())
+            },
+            Err(error) => {
+                error!("Couldn't load config file at {}: {}", &config_path, error);
+                exit(1);
             }
         };
 
-        if !config_path.is_file() {
-            error!("Selected config file is actually not a file.");
+        let mut config = Self {
+            config_path,
+            data,
+            fingerprint,
+        };
+
+        // Configs created before client infos got signed don't have an identity yet.
+        if config.data.keys.identity.is_empty() {
+            config.data.keys.identity = signing::generate_identity();
+        }
+
+        if config.flush().is_none() {
+            error!("Couldn't write config file at {}. See previous errors.", config.config_path);
             exit(1);
         }
 
-        Self {
-            config_path,
-            data,
-            fingerprint: String::new(),
+        if let Some((version, backup)) = migrated {
+            migration::report(config.config_path.as_std_path(), version, CONFIG_STEPS, &backup);
         }
+
+        config
     }
 
     pub fn verify_integrity(&mut self) {
         let mut faulty = false;
 
-        if self.data.version > CONFIG_VERSION {
-            warn!("Config file has been created in a newer version of mcsync. Current version = {}, config version = {}", CONFIG_VERSION, self.data.version);
-            faulty = true;
-        }
-
         // 1. Check if all synced server point to a valid server.
         // 2. Make sure those endpoints are reachable.
         for sync in &self.data.sync {
@@ -202,12 +188,22 @@ impl Config {
         }
     }
 
+    pub fn get_identity(&self) -> Ed25519KeyPair {
+        match signing::load_identity(&self.data.keys.identity) {
+            Some(identity) => identity,
+            None => {
+                error!("Your identity key is corrupt. Have you touched it?");
+                exit(1);
+            }
+        }
+    }
+
     pub fn get_data(&self) -> &ClientConfig {
         &self.data
     }
 
     pub fn add_server(&mut self, server_name: String, server: ServerInfo) -> Option<String> {
-        if server.version > SERVERINFO_VERSION {
+        if !server.is_supported() {
             error!("This server info file has been created with a newer version of mcsync. Refuse to load.");
             exit(1);
         }
@@ -220,9 +216,11 @@ impl Config {
             return None;
         }
 
+        // Importing the same server twice makes no sense. If the endpoint differs, someone may try to redirect our tunnel.
         let duplicate_pk = self.data.server.iter().find(|x| x.public_key == server.public_key);
-        if duplicate_pk.is_some() {
-            warn!("Already existing server \"{}\" has the same public key as this server.", duplicate_pk.unwrap().name);
+        if let Some(duplicate) = duplicate_pk {
+            error!("You already imported this server as \"{}\". Refuse to add it again.", duplicate.name);
+            return None;
         }
 
         self.data.server.push(ClientServer {
@@ -235,6 +233,7 @@ impl Config {
             tool_subnet: server.tool_subnet,
             ipv4_address: server.ipv4_address,
             dns: server.dns,
+            signing_key: server.signing_key.unwrap_or_default(),
         });
 
         match self.flush() {
@@ -263,7 +262,8 @@ impl Config {
             .find(|x| x.endpoint == endpoint)
     }
 
-    pub fn add_sync(&mut self, sync_name: &str, server_id: String, mut start: Utf8PathBuf) -> Option<String> {
+    /// Remembers a local copy of a game server. `sync_id` is the ID the backend assigned to it.
+    pub fn add_sync(&mut self, sync_name: &str, sync_id: String, server_id: String, mut start: Utf8PathBuf) -> Option<String> {
         if self.get_sync_by_name(sync_name).is_some() {
             error!("A sync with this name already exists.");
             return None;
@@ -283,7 +283,7 @@ impl Config {
             Some(ex) => {
                 if ex != "jar" {
                     // Try again but without extension
-                    return self.add_sync(sync_name, server_id, start.with_extension(""));
+                    return self.add_sync(sync_name, sync_id, server_id, start.with_extension(""));
                 }
 
                 // File points to a JAR-file.
@@ -323,23 +323,32 @@ impl Config {
             return None;
         }
 
-        let new_id = Uuid::new_v4();
         let mut location = final_path.clone();
         location.pop();
 
+        self.register_sync(sync_name, sync_id, server_id, location.to_string(), final_path.to_string())
+    }
+
+    /// Remembers a local copy of a game server without checking its start file. `start` may be empty if there is none.
+    pub fn register_sync(&mut self, sync_name: &str, sync_id: String, server_id: String, location: String, start: String) -> Option<String> {
+        if self.get_sync_by_name(sync_name).is_some() {
+            error!("A sync with this name already exists.");
+            return None;
+        }
+
         let config_entry = ClientSync {
-            id: new_id.to_string(),
+            id: sync_id.clone(),
             name: sync_name.to_string(),
-            location: location.to_string(),
+            location,
             server: server_id,
             share: true,
-            start: final_path.to_string()
+            start
         };
 
         self.data.sync.push(config_entry);
         self.flush();
 
-        None
+        Some(sync_id)
     }
     
     pub fn get_sync_by_name(&self, sync_name: &str) -> Option<ClientSync> {
@@ -347,53 +356,48 @@ impl Config {
             .find(|x| x.name == sync_name)
     }
 
+    pub fn get_sync_by_location(&self, location: &str) -> Option<ClientSync> {
+        self.data.clone().sync.into_iter()
+            .find(|x| x.location == location)
+    }
+
+    /// Writes the config to disk unless nothing changed since the last write.
+    /// Returns `None` if it couldn't be written, so changes only live in memory.
     pub fn flush(&mut self) -> Option<()> {
-        match serde_json::to_string_pretty(&self.data) {
-            Ok(pretty) => {
-                let hasher = match sha256_digest(BufReader::new(pretty.as_bytes())) {
-                    Ok(s) => s,
-                    Err(error) => {
-                        error!("Error: Cannot determine if config has changed: {}", error);
-                        exit(1);
-                    }
-                };
+        let pretty = match serde_json::to_string_pretty(&self.data) {
+            Ok(p) => p,
+            Err(error) => {
+                error!("Couldn't create JSON string: {}", error);
+                return None;
+            }
+        };
 
-                let new_fingerprint = HEXUPPER.encode(hasher.as_ref());
-                if new_fingerprint == self.fingerprint {
-                    warn!("No change was made.");
-                    return None;
-                }
+        let new_fingerprint = fingerprint(&pretty);
+        if new_fingerprint == self.fingerprint {
+            return Some(());
+        }
 
+        // Note that we assign chmod 660 to a new config file since it contains private keys.
+        match file::write_atomic(self.config_path.as_std_path(), pretty.as_bytes(), 0o660) {
+            Ok(_) => {
                 self.fingerprint = new_fingerprint;
-
-                match File::create(&self.config_path) {
-                    Ok(mut file) => match file.write_all(pretty.as_bytes()) {
-                        Ok(_) => match file.flush() {
-                            Ok(_) => { Some(()) }
-                            Err(error) => {
-                                error!("Error on flushing buffer to file: {}", error);
-                                None
-                            }
-                        },
-                        Err(error) => {
-                            error!("Error on writing buffer to file: {}", error);
-                            None
-                        }
-                    },
-
-                    Err(error) => {
-                        error!(
-                            "Config file ({}) couldn't be opened: {}",
-                            &self.config_path, error
-                        );
-                        None
-                    }
-                }
-            }
+                Some(())
+            },
             Err(error) => {
-                error!("Couldn't create JSON string: {}", error);
+                error!("Config file ({}) couldn't be written: {}", &self.config_path, error);
                 None
             }
         }
     }
 }
+
+/// Tells whether the config changed since it has been written last time.
+fn fingerprint(json: &str) -> String {
+    match sha256_digest(BufReader::new(json.as_bytes())) {
+        Ok(digest) => HEXUPPER.encode(digest.as_ref()),
+        Err(error) => {
+            error!("Error: Cannot determine if config has changed: {}", error);
+            exit(1);
+        }
+    }
+}
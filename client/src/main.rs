@@ -2,6 +2,7 @@ mod routines;
 mod prerequisites;
 mod platform;
 mod config;
+mod migration;
 mod sync;
 mod utils;
 
@@ -16,7 +17,7 @@ use nix::unistd::Uid;
 use paris::error;
 use prerequisites::Prerequisites;
 use platform::permission_check;
-use routines::{client_info::ClientInfo, import::Import, connect::Connect, disconnect::Disconnect, init::Init};
+use routines::{client_info::ShowClientInfo, import::Import, join::Join, connect::Connect, disconnect::Disconnect, init::Init, host::Host, start::Start, status::Status};
 
 #[derive(Parser, Debug)]
 #[clap(author = "Nicolas Klier aka Mondei1", version, about = "Tunnel & share your Minecraft server with friends.", long_about = None)]
@@ -59,7 +60,34 @@ enum Action {
         name: String,
 
         /// Location of .mcss file
-        path: Utf8PathBuf
+        path: Utf8PathBuf,
+
+        #[clap(long)]
+        /// Refuse the server info unless it has been signed by a server with this fingerprint.
+        fingerprint: Option<String>
+    },
+
+    /// Ask a server to let you in. Waits until an admin approved your request unless you got an invite.
+    ///
+    /// Without TLS, everyone on the way can read the invite and your preshared key, so only join over networks you trust.
+    Join {
+        /// Address of the server, e.g. "mc.example.com" or "http://mc.example.com:8081".
+        url: String,
+
+        /// Invite you got from the server owner.
+        invite: Option<String>,
+
+        #[clap(long)]
+        /// Name others will see. Defaults to your username.
+        name: Option<String>,
+
+        #[clap(long)]
+        /// Name for the new server in your configuration. Defaults to its hostname.
+        server_name: Option<String>,
+
+        #[clap(long)]
+        /// Refuse to join unless the server has this fingerprint. Required unless the address starts with "https://".
+        fingerprint: Option<String>
     },
 
     /// Print information about all game servers. Execute inside game server for more information.
@@ -72,6 +100,18 @@ enum Action {
 
         /// Path to a some script file (.sh/.bash) or to a .jar file.
         start_file: Utf8PathBuf
+    },
+
+    /// Pull the newest files of the game server in this folder and host it.
+    Host {
+        /// Clone this game server into a new folder if you don't have it yet.
+        name: Option<String>
+    },
+
+    /// Host a game server from anywhere. Changes get synced once it stops.
+    Start {
+        /// Name of the game server.
+        name: String
     }
 }
 
@@ -88,11 +128,14 @@ async fn main() {
             setup.check().await;
         }
         Action::ClientInfo => {
-            let ci = ClientInfo::new(conf);
+            let ci = ShowClientInfo::new(conf);
             ci.print();
         },
-        Action::Import { path, name } => {
-            Import::execute(conf, name, path);
+        Action::Import { path, name, fingerprint } => {
+            Import::execute(conf, name, path, fingerprint);
+        },
+        Action::Join { url, invite, name, server_name, fingerprint } => {
+            Join::execute(conf, url, invite, name, server_name, fingerprint).await;
         },
         Action::Connect { name } => {
             if !args.local {
@@ -114,10 +157,15 @@ async fn main() {
         },
         Action::Init { name, start_file } => {
             Init::execute(conf, name, start_file).await;
-        }
-        _ => {
-            error!("This command is not yet supported. Sorry :c");
-            exit(0);
+        },
+        Action::Host { name } => {
+            Host::execute(conf, name).await;
+        },
+        Action::Start { name } => {
+            Start::execute(conf, name).await;
+        },
+        Action::Status => {
+            Status::execute(conf).await;
         }
     }
 }
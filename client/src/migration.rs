@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use mcsync_proto::migration::{self, entries, fill, Step};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+/// Upgrade config.json by one version each. Append new ones, never change released ones.
+pub const CONFIG_STEPS: &[Step] = &[config_v1_to_v2];
+
+/// Upgrade the .sync file within each Minecraft server by one version each.
+pub const SYNC_STEPS: &[Step] = &[sync_v1_to_v2];
+
+/// Brings a file up to date. Also returns the version it had, if it had to be migrated.
+pub fn upgrade<T: DeserializeOwned>(mut value: Value, steps: &[Step]) -> Result<(T, Option<u16>), String> {
+    let from = migration::migrate(&mut value, steps)?;
+    let data = serde_json::from_value(value).map_err(|e| e.to_string())?;
+
+    Ok((data, from))
+}
+
+/// Stdout may be reserved for JSON (see `mcsync client-info`), so this goes to stderr.
+pub fn report(path: &Path, from: u16, steps: &[Step], backup: &Path) {
+    eprintln!("Upgraded {} from version {} to {}. The previous version is kept at {}.", path.display(), from, migration::latest(steps), backup.display());
+}
+
+/// Version 1 grew by adding fields that were filled with defaults while reading. Version 2 always contains all of them.
+fn config_v1_to_v2(config: &mut Map<String, Value>) -> Result<(), String> {
+    let keys = config.get_mut("keys").and_then(Value::as_object_mut).ok_or_else(|| String::from("keys are missing"))?;
+
+    // Generated by `Config::new`.
+    fill(keys, "identity", Value::from(""));
+
+    for server in entries(config, "server")? {
+        fill(server, "signing_key", Value::from(""));
+    }
+
+    Ok(())
+}
+
+/// Same as for the config. Syncs without ID have to be initialized again, `Sync::new` tells how.
+fn sync_v1_to_v2(sync: &mut Map<String, Value>) -> Result<(), String> {
+    fill(sync, "id", Value::from(""));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{config::{ClientConfig, CONFIG_VERSION}, sync::{SyncFile, SYNC_VERSION}};
+
+    use super::*;
+
+    /// Files as written by every historical version, oldest first.
+    const CONFIGS: &[(&str, &str)] = &[
+        ("v1-initial", include_str!("../tests/fixtures/config/v1-initial.json")),
+        ("v1", include_str!("../tests/fixtures/config/v1.json")),
+        ("v2", include_str!("../tests/fixtures/config/v2.json"))
+    ];
+
+    const SYNCS: &[(&str, &str)] = &[
+        ("v1-initial", include_str!("../tests/fixtures/sync/v1-initial.json")),
+        ("v1", include_str!("../tests/fixtures/sync/v1.json")),
+        ("v2", include_str!("../tests/fixtures/sync/v2.json"))
+    ];
+
+    fn parse(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn newest(fixtures: &[(&str, &str)]) -> Option<u64> {
+        fixtures.iter().map(|(_, json)| parse(json)["version"].as_u64().unwrap()).max()
+    }
+
+    #[test]
+    fn fixtures_cover_every_version() {
+        assert_eq!(newest(CONFIGS), Some(CONFIG_VERSION as u64));
+        assert_eq!(newest(SYNCS), Some(SYNC_VERSION as u64));
+    }
+
+    #[test]
+    fn upgrades_every_config() {
+        for (name, json) in CONFIGS {
+            let (config, from): (ClientConfig, _) = upgrade(parse(json), CONFIG_STEPS).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(config.version, CONFIG_VERSION, "{}", name);
+            assert_eq!(from.is_some(), *name != "v2", "{}", name);
+            assert_eq!(config.server[0].name, "friends", "{}", name);
+            assert_eq!(config.sync[0].name, "survival", "{}", name);
+        }
+
+        let (initial, _): (ClientConfig, _) = upgrade(parse(CONFIGS[0].1), CONFIG_STEPS).unwrap();
+        assert_eq!(initial.keys.identity, "");
+        assert_eq!(initial.server[0].signing_key, "");
+
+        let (signed, _): (ClientConfig, _) = upgrade(parse(CONFIGS[1].1), CONFIG_STEPS).unwrap();
+        assert_eq!(signed.server[0].signing_key, "mT2VqJ9xYk3n8Rw0cB1sE4dG7hL5pZ6aU0iO2fK8jN4=");
+    }
+
+    #[test]
+    fn upgrades_every_sync_file() {
+        for (name, json) in SYNCS {
+            let (sync, from): (SyncFile, _) = upgrade(parse(json), SYNC_STEPS).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(sync.version, SYNC_VERSION, "{}", name);
+            assert_eq!(from.is_some(), *name != "v2", "{}", name);
+            assert_eq!(sync.last_sync, 1660000160, "{}", name);
+        }
+
+        let (initial, _): (SyncFile, _) = upgrade(parse(SYNCS[0].1), SYNC_STEPS).unwrap();
+        assert_eq!(initial.id, "");
+    }
+
+    #[test]
+    fn current_versions_stay_untouched() {
+        let config = parse(CONFIGS[2].1);
+        let (data, from): (ClientConfig, _) = upgrade(config.clone(), CONFIG_STEPS).unwrap();
+        assert_eq!(from, None);
+        assert_eq!(serde_json::to_value(&data).unwrap(), config);
+
+        let sync = parse(SYNCS[2].1);
+        let (data, from): (SyncFile, _) = upgrade(sync.clone(), SYNC_STEPS).unwrap();
+        assert_eq!(from, None);
+        assert_eq!(serde_json::to_value(&data).unwrap(), sync);
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        let mut config = parse(CONFIGS[2].1);
+        config["version"] = json!(CONFIG_VERSION + 1);
+        assert!(upgrade::<ClientConfig>(config, CONFIG_STEPS).is_err());
+
+        let mut sync = parse(SYNCS[2].1);
+        sync["version"] = json!(SYNC_VERSION + 1);
+        assert!(upgrade::<SyncFile>(sync, SYNC_STEPS).is_err());
+    }
+}
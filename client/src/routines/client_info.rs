@@ -1,28 +1,52 @@
 use std::process::exit;
 
+use data_encoding::BASE64;
+use mcsync_proto::{info::{ClientInfo, CLIENT_INFO_VERSION}, signing::fingerprint};
 use paris::error;
-use serde::{Serialize, Deserialize};
 
-use crate::config::Config;
+use crate::{config::Config, utils::signing};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ClientInfo {
-    version: u16,
-    wireguard_pub: String
+/// Creates our client info, signed by our identity.
+pub fn create(config: &Config) -> ClientInfo {
+    let identity = config.get_identity();
+
+    let mut client_info = ClientInfo {
+        version: CLIENT_INFO_VERSION,
+        wireguard_pub: config.get_public_wireguard_key().to_base64(),
+        signing_key: None,
+        signature: None
+    };
+
+    let (signing_key, signature) = signing::sign(&identity, client_info.signed_message().as_bytes());
+    client_info.signing_key = Some(signing_key);
+    client_info.signature = Some(signature);
+
+    client_info
 }
 
-impl ClientInfo {
+pub struct ShowClientInfo {
+    client_info: ClientInfo
+}
+
+impl ShowClientInfo {
     pub fn new(config: Config) -> Self {
-        Self {
-            version: 1,
-            wireguard_pub: config.get_public_wireguard_key().to_base64()
-        }
+        Self { client_info: create(&config) }
+    }
+
+    /// Fingerprint of our identity. Server owners can check it using "mcsync-server accept --fingerprint".
+    pub fn fingerprint(&self) -> String {
+        let signing_key = self.client_info.signing_key.as_deref().unwrap_or_default();
+
+        fingerprint(&BASE64.decode(signing_key.as_bytes()).unwrap_or_default())
     }
 
     pub fn print(&self) {
-        match serde_json::to_string_pretty(self) {
+        match serde_json::to_string_pretty(&self.client_info) {
             Ok(json) => {
                 println!("{}", json);
+
+                // Stderr, so it's still visible if stdout goes into a .mcsc file.
+                eprintln!("Your fingerprint: {}", self.fingerprint());
             },
             Err(error) => {
                 error!("Cannot create json string for your client info: {}", error);
@@ -30,4 +54,4 @@ impl ClientInfo {
             }
         }
     }
-}
\ No newline at end of file
+}
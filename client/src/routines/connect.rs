@@ -6,9 +6,9 @@ use std::{
 };
 
 use camino::Utf8PathBuf;
-use paris::{error, success};
+use paris::{error, success, warn};
 
-use crate::{config::{ClientServer, Config}, platform::{get_wg_config, does_wg_interface_exist}};
+use crate::{config::{ClientServer, Config}, platform::{get_wg_config, does_wg_interface_exist}, sync::{Sync, BACKEND_URL}};
 
 pub struct Connect {}
 
@@ -121,12 +121,12 @@ PersistentKeepalive = 25
                     .user_agent("mcsync client")
                     .build().unwrap();
 
-                match client.get("http://backend.mc:8080").send().await {
+                match client.get(format!("{}/version", BACKEND_URL)).send().await {
                     Ok(res) => {
-                        if res.status() == 200 {
-                            success!("Connected with {}. Have fun playing!", &server.name);
-                        } else {
-                            error!("Connection succeeded but server backend returned with {}", res.status());
+                        // Playing works regardless, only syncing needs matching versions.
+                        match Sync::check_version(res, &[]).await {
+                            Some(version) => success!("Connected with {} (mcsync v{}). Have fun playing!", &server.name, version.version),
+                            None => warn!("Connected with {} but you won't be able to share or host game servers. See errors above.", &server.name)
                         }
                     }
                     Err(error) => {
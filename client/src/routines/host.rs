@@ -0,0 +1,134 @@
+use std::{process::exit, fs};
+
+use camino::Utf8PathBuf;
+use nix::unistd::getcwd;
+use mcsync_proto::version::features;
+use paris::{error, info, success, warn};
+
+use crate::{config::Config, sync::{self, Sync, SyncFile, SYNC_VERSION}, platform::is_connected, routines::start::Start};
+
+pub struct Host {
+}
+
+impl Host {
+    /// Without a name, the game server inside the current working directory gets updated and hosted.
+    /// With a name, the game server gets cloned into a new folder within the current working directory.
+    pub async fn execute(config: Config, sync_name: Option<String>) {
+        let cwd: Utf8PathBuf = match getcwd() {
+            Ok(c) => Utf8PathBuf::from_path_buf(c).unwrap(),
+            Err(error) => {
+                error!("Unable to obtain your current working directory: {}", error);
+                exit(1);
+            }
+        };
+
+        match sync_name {
+            Some(name) => Self::clone(config, cwd, name).await,
+            None => Self::host_local(config, cwd).await
+        }
+    }
+
+    async fn host_local(config: Config, cwd: Utf8PathBuf) {
+        if !cwd.join(".sync").exists() {
+            error!("There is no .sync file in this folder. Run \"mcsync host [NAME]\" to clone a game server.");
+            exit(1);
+        }
+
+        match config.get_sync_by_location(cwd.as_str()) {
+            Some(client_sync) => Start::run(config, client_sync).await,
+            None => {
+                error!("This game server is not registered in your config. Clone it again using \"mcsync host [NAME]\".");
+                exit(1);
+            }
+        }
+    }
+
+    async fn clone(mut config: Config, cwd: Utf8PathBuf, sync_name: String) {
+        if config.get_sync_by_name(&sync_name).is_some() {
+            error!("You already have a copy of {}. Run \"mcsync host\" within it to update it.", sync_name);
+            exit(1);
+        }
+
+        let current_server = match is_connected(&config) {
+            Some(s) => s,
+            None => {
+                error!("You need to connect with a server first!");
+                exit(1);
+            }
+        };
+
+        if Sync::check_backend(&[features::SYNC]).await.is_none() {
+            exit(1);
+        }
+
+        let remote = match Sync::list_remote().await {
+            Some(list) => match list.into_iter().find(|s| s.name == sync_name) {
+                Some(s) => s,
+                None => {
+                    error!("There is no game server called {} on {}.", sync_name, current_server.name);
+                    exit(1);
+                }
+            },
+            None => {
+                error!("Couldn't retrive game servers. See previous errors.");
+                exit(1);
+            }
+        };
+
+        let target = cwd.join(&sync_name);
+        info!("Clone `{}` into {} ...\n", sync_name, target);
+
+        if target.exists() {
+            error!("{} already exists. Remove it or clone from another folder.", target);
+            exit(1);
+        }
+
+        if let Err(error) = fs::create_dir_all(&target) {
+            error!("Cannot create directory at {}: {}", target, error);
+            exit(1);
+        }
+
+        // The local copy starts empty, so the first pull downloads everything.
+        let sync_file = SyncFile {
+            version: SYNC_VERSION,
+            id: remote.id.clone(),
+            server: current_server.id.clone(),
+            first_sync: 0,
+            last_sync: 0
+        };
+
+        if sync::store(&target.join(".sync"), &sync_file).is_none() {
+            exit(1);
+        }
+
+        let mut sync = match Sync::new(&config, target.clone()) {
+            Some(s) => s,
+            None => {
+                error!("Failed to initialize Minecraft server. See previous errors.");
+                exit(1);
+            }
+        };
+
+        if sync.pull().await.is_none() {
+            error!("Couldn't clone {}. Delete {} and try again.", sync_name, target);
+            exit(1);
+        }
+
+        let registered = match remote.start_file.is_empty() {
+            true => None,
+            false => config.add_sync(&sync_name, remote.id.clone(), current_server.id.clone(), target.join(&remote.start_file))
+        };
+
+        // Registered in any case, otherwise "mcsync host" within the copy doesn't know it.
+        if registered.is_none() {
+            warn!("{} has no usable start file. You have to start it manually.", sync_name);
+
+            if config.register_sync(&sync_name, remote.id, current_server.id, target.to_string(), String::new()).is_none() {
+                error!("Couldn't register {}. See previous errors.", sync_name);
+                exit(1);
+            }
+        }
+
+        success!("Done. You can now run \"mcsync host\" within the new server.");
+    }
+}
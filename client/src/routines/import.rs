@@ -2,13 +2,16 @@ use std::{process::exit, fs::File};
 
 use camino::Utf8PathBuf;
 use paris::{error, success, info};
-use crate::config::{Config, ServerInfo};
+use mcsync_proto::info::ServerInfo;
+
+use crate::{config::Config, utils::signing};
 
 pub struct Import {
 }
 
 impl Import {
-    pub fn execute(mut config: Config, server_name: String, target_file: Utf8PathBuf) {
+    /// Refuses the server info if it doesn't carry the `fingerprint` the server owner told us.
+    pub fn execute(mut config: Config, server_name: String, target_file: Utf8PathBuf, fingerprint: Option<String>) {
         if !target_file.exists() {
             error!("Selected server information file doesn't exist at {}", target_file.as_path().to_string());
             exit(1);
@@ -18,6 +21,8 @@ impl Import {
             Ok(file) => {
                 match serde_json::from_reader::<std::fs::File, ServerInfo>(file) {
                     Ok(si) => {
+                        signing::check_fingerprint(&si, fingerprint.as_deref());
+
                         match config.add_server(server_name.clone(), si) {
                             Some(uuid) => uuid,
                             None => {
@@ -1,13 +1,11 @@
 use std::{process::exit, fs::File, io::Write};
 
 use camino::Utf8PathBuf;
-use humansize::{format_size, DECIMAL};
 use nix::unistd::getcwd;
-use paris::{error, info};
+use mcsync_proto::version::features;
+use paris::{error, info, success};
 
-use crate::{config::Config, sync::{Sync, FileHash, SyncFile}, platform::is_connected};
-
-pub const MAX_PATH_LENGTH: usize = 80;
+use crate::{config::Config, sync::{Sync, SyncFile, SYNC_VERSION}, platform::is_connected};
 
 pub struct Init {
 }
@@ -35,6 +33,10 @@ impl Init {
             }
         };
 
+        if Sync::check_backend(&[features::SYNC]).await.is_none() {
+            exit(1);
+        }
+
         let mut sync_file = cwd.clone();
         sync_file.push(".sync");
 
@@ -43,10 +45,22 @@ impl Init {
             return;
         }
 
+        // Start file is stored relative to the server's root, so others can start it on their machine.
+        let relative_start = start_file.strip_prefix(&cwd).unwrap_or(&start_file).to_string();
+
+        let remote_id = match Sync::create_on_remote(&sync_name, &relative_start).await {
+            Some(id) => id,
+            None => {
+                error!("Couldn't register {} with the backend. See previous errors.", sync_name);
+                exit(1);
+            }
+        };
+
         match File::create(&sync_file) {
             Ok(mut file) => {
                 let default = SyncFile {
-                    version: 1,
+                    version: SYNC_VERSION,
+                    id: remote_id.clone(),
                     first_sync: 0,
                     last_sync: 0,
                     server: is_connected(&config).unwrap().id
@@ -68,7 +82,7 @@ impl Init {
                     }
                 }
 
-                config.add_sync(&sync_name, current_server.id, start_file);
+                config.add_sync(&sync_name, remote_id, current_server.id, start_file);
             }
             Err(error) => {
                 error!("Cannot create new .sync file within your Minecraft directory: {}", error);
@@ -76,7 +90,7 @@ impl Init {
             }
         }
 
-        let sync = match Sync::new(&config, cwd) {
+        let mut sync = match Sync::new(&config, cwd) {
             Some(s) => s,
             None => {
                 error!("Failed to initialize Minecraft server. See previous erros.");
@@ -84,48 +98,27 @@ impl Init {
             }
         };
 
-        let mut delta = match sync.negotiate_delta().await {
-            Some(d) => d,
-            None => {
-                error!("Couldn't negotiate delta with remote. See previous errors.");
-                exit(1);
-            }
-        };
-
-        let mut final_send: Vec<FileHash> = Vec::new();
-        final_send.append(&mut delta.new);
-        final_send.append(&mut delta.modified);
-
-        let file_amount = final_send.len();
-
-        for (sent, sync_file) in final_send.into_iter().enumerate() {
-            let progress = ((sent / file_amount * 100) as f64).round() as u32;
-            let mut print_progress = String::new();
-
-            // Format progress
-            if progress.to_string().len() < 3 {
-                for _ in 0..(3 - progress.to_string().len()) {
-                    print_progress += " ";
-                }
+        if sync.acquire_lease().await.is_none() {
+            error!("Couldn't sync {}. See previous errors.", sync_name);
+            exit(1);
+        }
 
-                print_progress += &progress.to_string();
-            }
+        // The first upload can take longer than the lease lasts.
+        let heartbeat = sync.spawn_heartbeat();
 
-            let mut print_path = sync_file.clone().path;
+        let pushed = sync.push().await;
 
-            // Cut of path at the start to save space
-            if print_path.len() > MAX_PATH_LENGTH {
-                let begin = print_path.len() - MAX_PATH_LENGTH;
-                let end = print_path.len();
+        heartbeat.abort();
+        sync.release_lease().await;
 
-                print_path = format!("...{}", print_path.get(begin..end).unwrap());
-            } else {
-                print_path = format!("{} ", print_progress);
+        match pushed {
+            Some(_) => {
+                success!("Synced {} with remote.", sync_name);
+            },
+            None => {
+                error!("Couldn't sync {}. See previous errors.", sync_name);
+                exit(1);
             }
-
-            println!("({} %) ↑ {} ({})", print_progress, print_path, format_size(sync_file.size, DECIMAL));
-
-            let _ = sync.transfer(&sync_file).await;
         }
     }
 }
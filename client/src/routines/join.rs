@@ -0,0 +1,170 @@
+use std::{process::exit, time::Duration};
+
+use mcsync_proto::{enrollment::{JoinRequest, JoinResponse}, info::ServerInfo};
+use paris::{error, info, success};
+use reqwest::{Client, Response, StatusCode, Url};
+
+use crate::{config::Config, utils::signing};
+
+use super::client_info;
+
+/// Port of the backend's enrollment listener unless the URL says otherwise.
+const ENROLLMENT_PORT: u16 = 8081;
+
+/// How often we ask whether the admin approved our request yet.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct Join {
+}
+
+impl Join {
+    /// Asks the server at `url` to let us in and waits until an admin approved it. No files need to be exchanged.
+    /// With an invite we are let in right away.
+    pub async fn execute(
+        mut config: Config, url: String, invite: Option<String>, client_name: Option<String>, server_name: Option<String>,
+        fingerprint: Option<String>
+    ) {
+        let url = match parse_url(&url) {
+            Some(u) => u,
+            None => {
+                error!("{} is not a valid address. Use something like \"mc.example.com\" or \"http://mc.example.com:8081\".", url);
+                exit(1);
+            }
+        };
+
+        // Anyone on the way could answer instead of the server. Its signature is the only thing we can trust then.
+        if url.scheme() == "http" && fingerprint.is_none() {
+            error!("{} doesn't use TLS, so the server has to prove who it is. Ask the server owner for its fingerprint and add --fingerprint.", url);
+            exit(1);
+        }
+
+        let server_name = server_name.unwrap_or_else(|| url.host_str().unwrap_or_default().to_string());
+        if config.get_server_by_name(&server_name).is_some() {
+            error!("A server with the name \"{}\" already exists. Choose another one using --server-name.", server_name);
+            exit(1);
+        }
+
+        let client_name = match client_name.or_else(|| users::get_current_username().and_then(|u| u.into_string().ok())) {
+            Some(n) => n,
+            None => {
+                error!("Couldn't figure out your name. Tell us using --name.");
+                exit(1);
+            }
+        };
+
+        let request = JoinRequest {
+            name: client_name.clone(),
+            client_info: client_info::create(&config),
+            invite
+        };
+
+        // Unlike the backend, the enrollment listener is out on the internet. Give it some time to answer.
+        let http = Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .user_agent("mcsync client")
+            .build().unwrap();
+
+        let server_info = match http.post(url.join("join").unwrap()).json(&request).send().await {
+            Ok(res) => {
+                match res.status() {
+                    // Our invite has been accepted.
+                    StatusCode::OK => Self::read_server_info(res, &client_name).await,
+                    StatusCode::ACCEPTED => {
+                        match res.json::<JoinResponse>().await {
+                            Ok(r) => Self::wait_for_approval(&http, &url, &r.token, &client_name).await,
+                            Err(error) => {
+                                error!("Server sent a faulty response: {}", error);
+                                exit(1);
+                            }
+                        }
+                    },
+                    status => {
+                        error!("Server refused to let you join ({}): {}", status, res.text().await.unwrap_or_default());
+                        exit(1);
+                    }
+                }
+            },
+            Err(error) => {
+                error!("Server doesn't seem reachable: {}", error);
+                exit(1);
+            }
+        };
+
+        signing::check_fingerprint(&server_info, fingerprint.as_deref());
+
+        let new_uuid = match config.add_server(server_name.clone(), server_info) {
+            Some(uuid) => uuid,
+            None => exit(1)
+        };
+
+        success!("You're in! Added server {} ({}) to your configuration.", server_name, new_uuid);
+        success!("You can now execute \"mcsync connect {}\" in order to play.\n", server_name);
+    }
+
+    async fn wait_for_approval(http: &Client, url: &Url, token: &str, client_name: &str) -> ServerInfo {
+        info!("Asked to join as {}. Waiting for an admin to approve it ...", client_name);
+        info!("They can do so using \"mcsync-server approve {}\". Press Ctrl+C to give up.", client_name);
+
+        let poll_url = url.join(&format!("join/{}", token)).unwrap();
+
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let res = match http.get(poll_url.clone()).send().await {
+                Ok(r) => r,
+                Err(error) => {
+                    // Keep waiting, the server might just restart.
+                    error!("Server doesn't seem reachable: {}", error);
+                    continue;
+                }
+            };
+
+            match res.status() {
+                StatusCode::OK => return Self::read_server_info(res, client_name).await,
+                StatusCode::ACCEPTED => continue,
+                StatusCode::NOT_FOUND => {
+                    error!("Your request to join has been denied.");
+                    exit(1);
+                },
+                status => {
+                    error!("Server responded with {} while waiting for approval.", status);
+                    exit(1);
+                }
+            }
+        }
+    }
+
+    async fn read_server_info(res: Response, client_name: &str) -> ServerInfo {
+        match res.json().await {
+            Ok(si) => si,
+            Err(error) => {
+                error!("Server sent a faulty server info: {}", error);
+                error!("Ask an admin for your server info using \"mcsync-server server-info {}\".", client_name);
+                exit(1);
+            }
+        }
+    }
+}
+
+/// Accepts plain hostnames as well. Those use the default enrollment port unless one is given.
+fn parse_url(url: &str) -> Option<Url> {
+    let mut url = if url.contains("://") {
+        Url::parse(url).ok()?
+    } else {
+        let mut url = Url::parse(&format!("http://{}", url)).ok()?;
+        if url.port().is_none() {
+            url.set_port(Some(ENROLLMENT_PORT)).ok()?;
+        }
+
+        url
+    };
+
+    url.host_str()?;
+
+    // Otherwise join() would replace the last path segment.
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+
+    Some(url)
+}
@@ -3,4 +3,8 @@ pub mod import;
 pub mod connect;
 pub mod disconnect;
 pub mod init;
-pub mod install;
\ No newline at end of file
+pub mod host;
+pub mod start;
+pub mod status;
+pub mod install;
+pub mod join;
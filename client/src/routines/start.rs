@@ -0,0 +1,135 @@
+use std::process::exit;
+
+use camino::Utf8PathBuf;
+use mcsync_proto::version::features;
+use paris::{error, info, success, warn};
+use tokio::process::Command;
+
+use crate::{config::{Config, ClientSync}, sync::Sync};
+
+pub struct Start {
+}
+
+impl Start {
+    pub async fn execute(config: Config, sync_name: String) {
+        let sync = match config.get_sync_by_name(&sync_name) {
+            Some(s) => s,
+            None => {
+                error!("There is no game server called {} on this machine. Run \"mcsync host {}\" to clone it.", sync_name, sync_name);
+                exit(1);
+            }
+        };
+
+        Self::run(config, sync).await;
+    }
+
+    /// Hosts a game server: acquire the lease, pull the newest files, run the server and push all changes once it stopped.
+    pub async fn run(config: Config, client_sync: ClientSync) {
+        let location = Utf8PathBuf::from(&client_sync.location);
+
+        if client_sync.start.is_empty() {
+            error!("{} has no start file, so it can't be hosted using mcsync. Start it manually.", client_sync.name);
+            exit(1);
+        }
+
+        let mut sync = match Sync::new(&config, location.clone()) {
+            Some(s) => s,
+            None => {
+                error!("Failed to load Minecraft server. See previous errors.");
+                exit(1);
+            }
+        };
+
+        if Sync::check_backend(&[features::SYNC, features::LEASES]).await.is_none() {
+            exit(1);
+        }
+
+        if sync.acquire_lease().await.is_none() {
+            exit(1);
+        }
+
+        // Pulling can take longer than the lease lasts.
+        let heartbeat = sync.spawn_heartbeat();
+
+        if sync.pull().await.is_none() {
+            error!("Couldn't pull the newest files. See previous errors.");
+            heartbeat.abort();
+            sync.release_lease().await;
+            exit(1);
+        }
+
+        if heartbeat.is_finished() {
+            error!("You lost the lease while pulling {}. Run \"mcsync start {}\" again once nobody else is hosting it.", client_sync.name, client_sync.name);
+            exit(1);
+        }
+
+        success!("You're now hosting {}. Stop the server as usual to sync your changes.", client_sync.name);
+        let clean_exit = Self::run_server(&client_sync, &location).await;
+
+        if !clean_exit {
+            warn!("Minecraft server didn't exit cleanly. Sync anyway ...");
+        }
+
+        if heartbeat.is_finished() {
+            error!("You lost the lease while hosting {}, so your changes cannot be pushed. They stay in {}.", client_sync.name, location);
+            exit(1);
+        }
+
+        let pushed = sync.push().await;
+
+        // Stopped right before releasing, otherwise it reports the released lease as lost.
+        heartbeat.abort();
+        sync.release_lease().await;
+
+        match pushed {
+            Some(generation) => success!("Synced {} with remote ({}). Others can host it now.", client_sync.name, generation),
+            None => {
+                error!("Couldn't sync your changes. See previous errors. Run \"mcsync start {}\" again to retry.", client_sync.name);
+                exit(1);
+            }
+        }
+    }
+
+    /// Runs the start script (or .jar) and waits until the Minecraft server stopped.
+    async fn run_server(client_sync: &ClientSync, location: &Utf8PathBuf) -> bool {
+        let mut command = if client_sync.start.ends_with(".jar") {
+            let mut c = Command::new("java");
+            c.args(["-Xms256M", "-Xmx2G", "-jar", &client_sync.start, "nogui"]);
+            c
+        } else {
+            cfg_if::cfg_if! {
+                if #[cfg(unix)] {
+                    let mut c = Command::new("sh");
+                    c.arg(format!("{}.sh", client_sync.start));
+                    c
+                }
+            }
+        };
+
+        let mut child = match command.current_dir(location).spawn() {
+            Ok(c) => c,
+            Err(error) => {
+                error!("Cannot start Minecraft server: {}", error);
+                return false;
+            }
+        };
+
+        // Ctrl+C reaches the Minecraft server as well. We stay alive to sync once it's done saving.
+        loop {
+            tokio::select! {
+                status = child.wait() => {
+                    return match status {
+                        Ok(s) => s.success(),
+                        Err(error) => {
+                            error!("Failed to unwrap exit code: {}", error);
+                            false
+                        }
+                    };
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Waiting for Minecraft server to stop ...");
+                }
+            }
+        }
+    }
+}
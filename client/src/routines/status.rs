@@ -0,0 +1,120 @@
+use std::process::exit;
+
+use camino::Utf8PathBuf;
+use nix::unistd::getcwd;
+use mcsync_proto::{time::format_ago, version::features};
+use paris::{error, info, warn};
+
+use crate::{config::Config, sync::Sync, platform::is_connected};
+
+pub struct Status {
+}
+
+impl Status {
+    pub async fn execute(config: Config) {
+        let current_server = match is_connected(&config) {
+            Some(s) => s,
+            None => {
+                error!("You need to connect with a server first!");
+                exit(1);
+            }
+        };
+
+        if Sync::check_backend(&[features::STATUS]).await.is_none() {
+            exit(1);
+        }
+
+        let status = match Sync::status_remote().await {
+            Some(s) => s,
+            None => {
+                error!("Couldn't retrive status. See previous errors.");
+                exit(1);
+            }
+        };
+
+        let headline = format!("Connected with `{}` (mcsync v{})", current_server.name, status.version);
+        println!("\n{}\n{}\n", headline, "=".repeat(headline.chars().count()));
+
+        println!("Available servers:");
+        if status.servers.is_empty() {
+            println!("  There are no game servers yet. Create one using \"mcsync init\".");
+        }
+
+        for server in &status.servers {
+            match &server.host {
+                Some(host) => println!(
+                    "  * {} -> {} / {} players online - {} (hosted by {})",
+                    server.name, server.online_players, server.max_players,
+                    server.version.as_deref().unwrap_or("unknown version"), host
+                ),
+                None if server.last_host.is_empty() => println!("  - {}", server.name),
+                None => println!("  - {} (last hosted by {} {})", server.name, server.last_host, format_ago(server.last_hosted))
+            }
+        }
+
+        println!("\nClients:");
+        for member in &status.members {
+            if member.online {
+                println!("  * {} (online)", member.name);
+            } else if member.last_seen == 0 {
+                println!("  - {} (never seen)", member.name);
+            } else {
+                println!("  - {} (last seen {})", member.name, format_ago(member.last_seen));
+            }
+        }
+
+        if let Ok(cwd) = getcwd() {
+            let cwd = Utf8PathBuf::from_path_buf(cwd).unwrap();
+
+            if cwd.join(".sync").exists() {
+                println!();
+                Self::local_status(&config, cwd).await;
+            }
+        }
+    }
+
+    /// Compares the game server in the current working directory with its remote counterpart.
+    async fn local_status(config: &Config, cwd: Utf8PathBuf) {
+        let sync = match Sync::new(config, cwd) {
+            Some(s) => s,
+            None => return
+        };
+
+        let manifest = match sync.fetch_manifest().await {
+            Some(m) => m,
+            None => return
+        };
+
+        let changes = sync.local_changes(&manifest);
+
+        println!("This folder:");
+        println!("  Local generation:  {}", sync.last_sync());
+        println!("  Remote generation: {}", manifest.generation);
+
+        if sync.last_sync() < manifest.generation {
+            warn!("Someone synced in the meantime. Run \"mcsync host\" to pull the newest files.");
+        }
+
+        if changes.new.is_empty() && changes.modified.is_empty() && changes.removed.is_empty() {
+            info!("No pending changes.");
+            return;
+        }
+
+        println!(
+            "  Pending changes:   {} new, {} modified, {} removed",
+            changes.new.len(), changes.modified.len(), changes.removed.len()
+        );
+
+        for file in &changes.new {
+            println!("    + {}", file.path);
+        }
+
+        for file in &changes.modified {
+            println!("    ~ {}", file.path);
+        }
+
+        for file in &changes.removed {
+            println!("    - {}", file.path);
+        }
+    }
+}
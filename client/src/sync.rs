@@ -1,55 +1,41 @@
-use std::{fs::File, time::Duration};
+use std::{fs::File, time::Duration, collections::HashMap};
 
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use data_encoding::HEXLOWER;
+use futures::StreamExt;
+use mcsync_proto::{
+    status::StatusResponse,
+    sync::{CreateServer, CreateServerResponse, DeltaClient, DeltaServer, FileHash, LeaseResponse, Manifest, ServerEntry, TransferResponse},
+    version::{Compatibility, VersionResponse, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION}
+};
 use paris::{error, warn, info};
-use reqwest::{Client, Body};
+use reqwest::{Client, Body, Response, StatusCode, Url};
+use ring::digest::{Context, SHA256};
 use serde::{Serialize, Deserialize};
+use tokio::{io::AsyncWriteExt, task::JoinHandle};
 use tokio_util::codec::{BytesCodec, FramedRead};
 use walkdir::WalkDir;
 
-use crate::{utils::hash::sha256_digest, config::{Config, ClientServer}};
+use crate::{utils::{file, hash::sha256_digest, progress::print_transfer}, config::Config, migration::{self, SYNC_STEPS}};
 
-pub const SYNC_VERSION: u16 = 1;
+pub const SYNC_VERSION: u16 = mcsync_proto::migration::latest(SYNC_STEPS);
 
-// === [ BEGIN HTTP JSON TYPES ] ===
+/// mcsync's backend is only reachable through the tunnel.
+pub const BACKEND_URL: &str = "http://backend.mc:8080";
 
-#[derive(Serialize, Deserialize)]
-pub struct CreateServer {
-    server_name: String
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct CreateServerResponse {
-    server_uuid: String
-}
+/// Hosts renew their lease this often. The backend drops leases after 90 seconds without heartbeat.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct FileHash {
-    pub(crate) id: u32,
-    pub(crate) size: u64,
-    pub(crate) path: String,
-    pub(crate) hash: String
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct DeltaClient {
-    files: Vec<FileHash>,
-    last_sync: u64
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct DeltaServer {
-    pub(crate) new: Vec<FileHash>,
-    pub(crate) modified: Vec<FileHash>,
-    pub(crate) removed: Vec<FileHash>
-}
-
-// === [ END HTTP JSON TYPES ] ===
+/// Files are downloaded next to their final location using this suffix and renamed once verified.
+const DOWNLOAD_SUFFIX: &str = ".mcsync-download";
 
 #[derive(Serialize, Deserialize)]
 pub struct SyncFile {
     pub(crate) version: u16,
+
+    /// ID of the game server on the backend.
+    pub(crate) id: String,
+
     pub(crate) server: String,
     pub(crate) first_sync: u64,
     pub(crate) last_sync: u64
@@ -57,7 +43,6 @@ pub struct SyncFile {
 
 pub struct Sync {
     sync: SyncFile,
-    server: ClientServer,
     minecraft_server_path: Utf8PathBuf,
     http_client: Client
 }
@@ -81,8 +66,8 @@ impl Sync {
         let mut sync_file = path.clone();
         sync_file.push(".sync");
 
-        let sync: SyncFile = if sync_file.exists() {
-            match File::open(sync_file) {
+        let value: serde_json::Value = if sync_file.exists() {
+            match File::open(&sync_file) {
                 Ok(file) => {
                     match serde_json::from_reader(file) {
                         Ok(json) => json,
@@ -102,38 +87,163 @@ impl Sync {
             return None;
         };
 
-        if sync.version > SYNC_VERSION {
-            error!("This sync has been made with a newer version of mcsync. Please upgrade.");
-            return None;
-        }
-
-        let server = match config.get_server_by_id(&sync.server) {
-            Some(s) => s,
-            None => {
-                error!("The .sync file points to a server that doesn't exist. Do you removed the server?");
+        let (sync, migrated) = match migration::upgrade::<SyncFile>(value, SYNC_STEPS) {
+            Ok(upgraded) => upgraded,
+            Err(error) => {
+                error!("Cannot read .sync within your Minecraft directory: {}", error);
                 return None;
             }
         };
 
-        let client = reqwest::ClientBuilder::new()
-                    .connect_timeout(Duration::from_millis(200))
-                    .user_agent("mcsync client")
-                    .build().unwrap();
+        if let Some(version) = migrated {
+            let backup = match file::keep_before_migration(sync_file.as_std_path(), version) {
+                Ok(b) => b,
+                Err(error) => {
+                    error!("Couldn't keep a copy of .sync before upgrading it: {}", error);
+                    return None;
+                }
+            };
+
+            store(&sync_file, &sync)?;
+            migration::report(sync_file.as_std_path(), version, SYNC_STEPS, &backup);
+        }
+
+        if sync.id.is_empty() {
+            error!("This Minecraft server has never been registered with the backend. Delete .sync and run \"mcsync init\" again.");
+            return None;
+        }
+
+        if config.get_server_by_id(&sync.server).is_none() {
+            error!("The .sync file points to a server that doesn't exist. Do you removed the server?");
+            return None;
+        }
 
         Some(Self {
             minecraft_server_path: path,
-            http_client: client,
-            server,
+            http_client: http_client(),
             sync
         })
     }
 
-    pub async fn create_on_remote(&self) -> Option<String> {
+    /// Lists all game servers known to the backend.
+    pub async fn list_remote() -> Option<Vec<ServerEntry>> {
+        match http_client().get(format!("{}/server", BACKEND_URL)).send().await {
+            Ok(res) => {
+                if !res.status().is_success() {
+                    error!("Couldn't retrive game servers from backend: {}", res.status());
+                    return None;
+                }
+
+                match res.json().await {
+                    Ok(s) => Some(s),
+                    Err(error) => {
+                        error!("Server sent a faulty response: {}", error);
+                        None
+                    }
+                }
+            }
+            Err(error) => {
+                error!("Server doesn't seem reachable: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Makes sure we speak the backend's protocol and it offers all `features` before we talk any further.
+    pub async fn check_backend(features: &[&str]) -> Option<VersionResponse> {
+        match http_client().get(format!("{}/version", BACKEND_URL)).send().await {
+            Ok(res) => Self::check_version(res, features).await,
+            Err(error) => {
+                error!("Server doesn't seem reachable: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Evaluates the backend's answer to `GET /version`.
+    pub async fn check_version(res: Response, features: &[&str]) -> Option<VersionResponse> {
+        // Backends older than the version endpoint don't know it.
+        if res.status() == StatusCode::NOT_FOUND {
+            error!("The server runs an mcsync version that is too old for this client. Ask the server owner to upgrade the server.");
+            return None;
+        }
+
+        if res.status() == StatusCode::FORBIDDEN {
+            error!("The server doesn't know you (anymore). Ask the server owner whether you have been removed.");
+            return None;
+        }
+
+        if !res.status().is_success() {
+            error!("Couldn't retrive version from backend: {}", res.status());
+            return None;
+        }
+
+        let version: VersionResponse = match res.json().await {
+            Ok(v) => v,
+            Err(error) => {
+                error!("Server sent a faulty response: {}", error);
+                return None;
+            }
+        };
+
+        match version.compatibility() {
+            Compatibility::Compatible => {},
+            Compatibility::UpgradeUs => {
+                error!(
+                    "The server runs mcsync v{} which requires a newer client (protocol {} or higher, yours speaks {}). Please upgrade mcsync.",
+                    version.version, version.protocol_min, PROTOCOL_VERSION
+                );
+                return None;
+            },
+            Compatibility::UpgradeThem => {
+                error!(
+                    "The server runs mcsync v{} which is too old for this client (protocol {} or lower, yours needs {}). Ask the server owner to upgrade the server.",
+                    version.version, version.protocol_max, MIN_PROTOCOL_VERSION
+                );
+                return None;
+            }
+        }
+
+        if let Some(missing) = features.iter().find(|f| !version.supports(f)) {
+            error!("The server runs mcsync v{} which doesn't support \"{}\" yet. Ask the server owner to upgrade the server.", version.version, missing);
+            return None;
+        }
+
+        Some(version)
+    }
+
+    /// Asks the backend which game servers are hosted right now and who is online.
+    pub async fn status_remote() -> Option<StatusResponse> {
+        match http_client().get(format!("{}/status", BACKEND_URL)).send().await {
+            Ok(res) => {
+                if !res.status().is_success() {
+                    error!("Couldn't retrive status from backend: {}", res.status());
+                    return None;
+                }
+
+                match res.json().await {
+                    Ok(s) => Some(s),
+                    Err(error) => {
+                        error!("Server sent a faulty response: {}", error);
+                        None
+                    }
+                }
+            }
+            Err(error) => {
+                error!("Server doesn't seem reachable: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Registers a new game server with the backend. Returns its ID.
+    pub async fn create_on_remote(server_name: &str, start_file: &str) -> Option<String> {
         let server = CreateServer {
-            server_name: self.sync.server.clone()
+            server_name: server_name.to_string(),
+            start_file: start_file.to_string()
         };
 
-        let req = self.http_client.post("http://backend.mc/server")
+        let req = http_client().post(format!("{}/server", BACKEND_URL))
             .json(&server)
             .send()
             .await;
@@ -163,8 +273,8 @@ impl Sync {
         }
     }
 
-    pub async fn negotiate_delta(&self) -> Option<DeltaServer> {
-        // Looks like this: "[HASH] [PATH]"
+    /// Hashes every file of the local copy.
+    pub fn local_files(&self) -> Vec<FileHash> {
         let mut files: Vec<FileHash> = Vec::new();
 
         info!("Compute local hashes ...");
@@ -185,6 +295,14 @@ impl Sync {
                 continue;
             }
 
+            let relative_path = path.strip_prefix(&self.minecraft_server_path).unwrap().to_string();
+
+            // The .sync file (and copies of older versions) describes the local copy and is never synced.
+            // Same goes for unfinished downloads.
+            if relative_path == ".sync" || relative_path.starts_with(".sync.") || relative_path.ends_with(DOWNLOAD_SUFFIX) {
+                continue;
+            }
+
             let mut file = match File::open(entry.path()) {
                 Ok(f) => f,
                 Err(error) => {
@@ -206,11 +324,10 @@ impl Sync {
             let file = FileHash {
                 id,
                 size: file.metadata().unwrap().len(),
-                path: path.strip_prefix(&self.minecraft_server_path).unwrap().to_string(),
+                path: relative_path,
                 hash: final_hash
             };
 
-            println!("{} {}", file.hash, file.path);
             files.push(file);
 
             id += 1;
@@ -218,24 +335,33 @@ impl Sync {
 
         info!("Done. Processed {} files.", files.len());
 
+        files
+    }
+
+    pub async fn negotiate_delta(&self) -> Option<DeltaServer> {
+        let files = self.local_files();
+
         let server_request_body = DeltaClient {
             files,
             last_sync: self.sync.last_sync
         };
 
-        let req = self.http_client.post(format!("http://backend.mc/server/{}/delta", self.server.id))
+        let req = self.http_client.post(format!("{}/server/{}/delta", BACKEND_URL, self.sync.id))
             .json(&server_request_body)
             .send()
             .await;
 
         let delta: DeltaServer = match req {
             Ok(res) => {
-                if !res.status().is_success() {
-                    error!("Couldn't retrive delta from server: {}", res.status());
+                if res.status() == StatusCode::FORBIDDEN {
+                    error!("You may not push changes to this game server: {}", res.text().await.unwrap_or_default());
                     return None;
                 } else if res.status().as_u16() == 409 {
                     error!("There is a conflict! The server already has a newer version. Unfortunately there is now way to resolve conflicts right now. As for now you cannot sync.");
                     return None;
+                } else if !res.status().is_success() {
+                    error!("Couldn't retrive delta from server: {}", res.status());
+                    return None;
                 }
 
                 match res.json().await {
@@ -258,12 +384,12 @@ impl Sync {
     }
 
     // At this point, the server grants our IP to send over the new files. No need for authentication.
-    pub async fn transfer(&self, sync_file: &FileHash) -> bool {
-        let file = match tokio::fs::File::open(&sync_file.path).await {
+    pub async fn transfer(&self, sync_file: &FileHash) -> Option<TransferResponse> {
+        let file = match tokio::fs::File::open(self.minecraft_server_path.join(&sync_file.path)).await {
             Ok(f) => f,
             Err(error) => {
                 error!("File {} got deleted/moved while sync is in process: {}", &sync_file.path, error);
-                return false;
+                return None;
             }
         };
 
@@ -271,7 +397,7 @@ impl Sync {
         let body = Body::wrap_stream(stream);
 
         let req = self.http_client
-            .post(format!("http://backend.mc/server/{}/transfer/{}", self.sync.server, sync_file.id))
+            .post(format!("{}/server/{}/transfer/{}", BACKEND_URL, self.sync.id, sync_file.id))
             .body(body)
             .send()
             .await;
@@ -279,16 +405,378 @@ impl Sync {
         match req {
             Ok(res) => {
                 if res.status().is_success() {
-                    return true;
+                    return match res.json().await {
+                        Ok(r) => Some(r),
+                        Err(error) => {
+                            error!("Server sent a faulty response: {}", error);
+                            None
+                        }
+                    };
                 }
 
                 error!("Server respond with error code {}", res.status());
+                None
+            }
+            Err(error) => {
+                error!("Request failed: {}", error);
+                None
+            }
+        }
+    }
+
+    pub async fn fetch_manifest(&self) -> Option<Manifest> {
+        let req = self.http_client.get(format!("{}/server/{}/manifest", BACKEND_URL, self.sync.id))
+            .send()
+            .await;
+
+        match req {
+            Ok(res) => {
+                if res.status() == StatusCode::FORBIDDEN {
+                    error!("You may not play on this game server: {}", res.text().await.unwrap_or_default());
+                    return None;
+                } else if !res.status().is_success() {
+                    error!("Couldn't retrive file list from server: {}", res.status());
+                    return None;
+                }
+
+                match res.json().await {
+                    Ok(m) => Some(m),
+                    Err(error) => {
+                        error!("Server sent a faulty response: {}", error);
+                        None
+                    }
+                }
+            }
+            Err(error) => {
+                error!("Server doesn't seem reachable: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Acquires the exclusive right to host this game server. Fails if someone else is hosting.
+    pub async fn acquire_lease(&self) -> Option<LeaseResponse> {
+        let req = self.http_client.post(format!("{}/server/{}/lease", BACKEND_URL, self.sync.id))
+            .send()
+            .await;
+
+        match req {
+            Ok(res) => {
+                if res.status().as_u16() == 423 {
+                    match res.json::<LeaseResponse>().await {
+                        Ok(lease) => error!("{} is hosting this server right now. Join them instead!", lease.holder),
+                        Err(_) => error!("Someone else is hosting this server right now.")
+                    }
+
+                    return None;
+                } else if res.status() == StatusCode::FORBIDDEN {
+                    error!("You may not host this game server: {}", res.text().await.unwrap_or_default());
+                    return None;
+                } else if !res.status().is_success() {
+                    error!("Couldn't acquire lease: {}", res.status());
+                    return None;
+                }
+
+                match res.json().await {
+                    Ok(l) => Some(l),
+                    Err(error) => {
+                        error!("Server sent a faulty response: {}", error);
+                        None
+                    }
+                }
+            }
+            Err(error) => {
+                error!("Server doesn't seem reachable: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Keeps the lease alive in the background until the returned task gets aborted.
+    /// The task ends early once the backend refuses to renew the lease, see `JoinHandle::is_finished`.
+    pub fn spawn_heartbeat(&self) -> JoinHandle<()> {
+        let client = self.http_client.clone();
+        let url = format!("{}/server/{}/lease/heartbeat", BACKEND_URL, self.sync.id);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                match client.post(&url).send().await {
+                    Ok(res) if res.status().is_client_error() => {
+                        error!("You lost the lease of this game server: {}", res.text().await.unwrap_or_default());
+                        error!("Others might be hosting it now. Stop the Minecraft server, your changes cannot be pushed anymore.");
+                        return;
+                    }
+                    Ok(res) => {
+                        if !res.status().is_success() {
+                            warn!("Backend couldn't renew your lease ({}). Trying again ...", res.status());
+                        }
+                    }
+                    Err(error) => {
+                        warn!("Couldn't renew lease: {}", error);
+                    }
+                }
+            }
+        })
+    }
+
+    pub async fn release_lease(&self) -> bool {
+        let req = self.http_client.delete(format!("{}/server/{}/lease", BACKEND_URL, self.sync.id))
+            .send()
+            .await;
+
+        match req {
+            Ok(res) => {
+                if res.status().is_success() {
+                    return true;
+                }
+
+                error!("Couldn't release lease: {}", res.status());
                 false
             }
+            Err(error) => {
+                error!("Server doesn't seem reachable: {}", error);
+                false
+            }
+        }
+    }
+
+    /// Uploads all local changes. Requires the lease. Returns the new generation.
+    pub async fn push(&mut self) -> Option<u64> {
+        let mut delta = self.negotiate_delta().await?;
+        let mut generation = delta.generation;
+
+        let mut final_send: Vec<FileHash> = Vec::new();
+        final_send.append(&mut delta.new);
+        final_send.append(&mut delta.modified);
+
+        let file_amount = final_send.len();
+
+        for (sent, sync_file) in final_send.into_iter().enumerate() {
+            print_transfer(sent, file_amount, "↑", &sync_file.path, sync_file.size);
+
+            match self.transfer(&sync_file).await {
+                Some(response) => {
+                    if response.generation.is_some() {
+                        generation = response.generation;
+                    }
+                },
+                None => {
+                    error!("Transfer of {} failed. Your changes have not been synced.", sync_file.path);
+                    return None;
+                }
+            }
+        }
+
+        match generation {
+            Some(g) => {
+                self.set_last_sync(g)?;
+                Some(g)
+            },
+            None => {
+                error!("Remote didn't confirm the sync. Your changes have not been synced.");
+                None
+            }
+        }
+    }
+
+    /// Brings the local copy to the newest generation of the backend.
+    /// Missing and modified files are downloaded, files that have been removed remotely get deleted.
+    /// Returns the generation the local copy is now based on.
+    pub async fn pull(&mut self) -> Option<u64> {
+        let manifest = self.fetch_manifest().await?;
+
+        if manifest.generation == self.sync.last_sync {
+            info!("Your local copy is already up to date.");
+            return Some(manifest.generation);
+        }
+
+        if manifest.generation < self.sync.last_sync {
+            error!("Your local copy is newer than the backend's. Something is off, refuse to pull.");
+            return None;
+        }
+
+        if self.sync.last_sync != 0 {
+            warn!("Local changes since your last sync will be overwritten by the newer version of the backend.");
+        }
+
+        let local: HashMap<String, FileHash> = self.local_files().into_iter()
+            .map(|f| (f.path.clone(), f))
+            .collect();
+
+        let download: Vec<&FileHash> = manifest.files.iter()
+            .filter(|f| local.get(&f.path).map(|l| l.hash != f.hash).unwrap_or(true))
+            .collect();
+
+        let removed: Vec<&String> = local.keys()
+            .filter(|path| !manifest.files.iter().any(|f| &&f.path == path))
+            .collect();
+
+        info!("Delta summary: {} files to download and {} files to delete.", download.len(), removed.len());
+
+        for (received, file) in download.iter().enumerate() {
+            print_transfer(received, download.len(), "↓", &file.path, file.size);
+
+            if !self.download(file).await {
+                error!("Download of {} failed. Your local copy is incomplete, run the command again.", file.path);
+                return None;
+            }
+        }
+
+        for path in removed {
+            if let Err(error) = std::fs::remove_file(self.minecraft_server_path.join(path)) {
+                warn!("Couldn't delete {}: {}", path, error);
+            }
+        }
+
+        self.set_last_sync(manifest.generation)?;
+
+        Some(manifest.generation)
+    }
+
+    /// Downloads a single file of the current generation. The file only replaces the local one if its hash matches.
+    async fn download(&self, file: &FileHash) -> bool {
+        let mut url = Url::parse(BACKEND_URL).unwrap();
+        url.path_segments_mut().unwrap()
+            .extend(["server", &self.sync.id, "file"])
+            .extend(file.path.split('/'));
+
+        let res = match self.http_client.get(url).send().await {
+            Ok(r) => r,
             Err(error) => {
                 error!("Request failed: {}", error);
+                return false;
+            }
+        };
+
+        if !res.status().is_success() {
+            error!("Server respond with error code {}", res.status());
+            return false;
+        }
+
+        let target = self.minecraft_server_path.join(&file.path);
+        let partial = Utf8PathBuf::from(format!("{}{}", target, DOWNLOAD_SUFFIX));
+
+        if let Some(parent) = target.parent() {
+            if let Err(error) = tokio::fs::create_dir_all(parent).await {
+                error!("Cannot create directory {}: {}", parent, error);
+                return false;
+            }
+        }
+
+        let mut output = match tokio::fs::File::create(&partial).await {
+            Ok(f) => f,
+            Err(error) => {
+                error!("Cannot create {}: {}", partial, error);
+                return false;
+            }
+        };
+
+        let mut context = Context::new(&SHA256);
+        let mut stream = res.bytes_stream();
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(error) => {
+                    error!("Download interrupted: {}", error);
+                    let _ = tokio::fs::remove_file(&partial).await;
+                    return false;
+                }
+            };
+
+            context.update(&chunk);
+
+            if let Err(error) = output.write_all(&chunk).await {
+                error!("I/O error for file {}: {}", partial, error);
+                let _ = tokio::fs::remove_file(&partial).await;
+                return false;
+            }
+        }
+
+        let _ = output.flush().await;
+
+        if HEXLOWER.encode(context.finish().as_ref()) != file.hash {
+            error!("{} doesn't match the expected hash. Did someone sync while you were downloading?", file.path);
+            let _ = tokio::fs::remove_file(&partial).await;
+            return false;
+        }
+
+        match tokio::fs::rename(&partial, &target).await {
+            Ok(_) => true,
+            Err(error) => {
+                error!("Cannot move {} to {}: {}", partial, target, error);
                 false
             }
         }
     }
+
+    /// Generation the local copy is based on.
+    pub fn last_sync(&self) -> u64 {
+        self.sync.last_sync
+    }
+
+    /// Compares the local copy with `manifest` without transferring anything.
+    pub fn local_changes(&self, manifest: &Manifest) -> DeltaServer {
+        let local = self.local_files();
+        let remote: HashMap<&str, &FileHash> = manifest.files.iter().map(|f| (f.path.as_str(), f)).collect();
+
+        let mut delta = DeltaServer { new: vec![], modified: vec![], removed: vec![], generation: None };
+
+        for file in &local {
+            match remote.get(file.path.as_str()) {
+                None => delta.new.push(file.clone()),
+                Some(r) if r.hash != file.hash => delta.modified.push(file.clone()),
+                _ => {}
+            }
+        }
+
+        let local_paths: HashMap<&str, ()> = local.iter().map(|f| (f.path.as_str(), ())).collect();
+        delta.removed = manifest.files.iter()
+            .filter(|f| !local_paths.contains_key(f.path.as_str()))
+            .cloned()
+            .collect();
+
+        delta
+    }
+
+    /// Remembers the generation our local copy is based on. Required to push again later on.
+    pub fn set_last_sync(&mut self, generation: u64) -> Option<()> {
+        if self.sync.first_sync == 0 {
+            self.sync.first_sync = generation;
+        }
+
+        self.sync.last_sync = generation;
+
+        store(&self.minecraft_server_path.join(".sync"), &self.sync)
+    }
+}
+
+/// Writes a .sync file. A crash leaves either the old or the new version, never a truncated one.
+pub fn store(path: &Utf8Path, sync: &SyncFile) -> Option<()> {
+    let json = match serde_json::to_string_pretty(sync) {
+        Ok(j) => j,
+        Err(error) => {
+            error!("Failed to create JSON object for .sync file: {}", error);
+            return None;
+        }
+    };
+
+    match file::write_atomic(path.as_std_path(), json.as_bytes(), 0o644) {
+        Ok(_) => Some(()),
+        Err(error) => {
+            error!("Failed to write to .sync: {}", error);
+            None
+        }
+    }
+}
+
+pub fn http_client() -> Client {
+    reqwest::ClientBuilder::new()
+        .connect_timeout(Duration::from_millis(200))
+        .user_agent("mcsync client")
+        .build().unwrap()
 }
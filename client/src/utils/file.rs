@@ -0,0 +1,27 @@
+use std::{
+    fs::{File, Metadata},
+    io,
+    os::unix::fs::{fchown, MetadataExt},
+    path::Path
+};
+
+use serde::de::DeserializeOwned;
+
+use mcsync_proto::file;
+
+pub use mcsync_proto::file::keep_before_migration;
+
+/// See `mcsync_proto::file::write_atomic`. The file keeps its owner.
+pub fn write_atomic(path: &Path, contents: &[u8], mode: u32) -> io::Result<()> {
+    file::write_atomic_with(path, contents, mode, keep_owner)
+}
+
+/// See `mcsync_proto::file::read_json`. A restored file keeps its owner.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<(T, String)>, String> {
+    file::read_json_with(path, keep_owner)
+}
+
+/// Some commands run using sudo. The config still has to belong to the user afterwards.
+fn keep_owner(file: &File, metadata: &Metadata) {
+    let _ = fchown(file, Some(metadata.uid()), Some(metadata.gid()));
+}
@@ -1,3 +1,6 @@
 pub mod hash;
 pub mod rclone;
-pub mod child;
\ No newline at end of file
+pub mod child;
+pub mod progress;
+pub mod file;
+pub mod signing;
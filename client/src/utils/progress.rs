@@ -0,0 +1,21 @@
+use humansize::{format_size, DECIMAL};
+
+pub const MAX_PATH_LENGTH: usize = 80;
+
+/// Prints one line of a transfer, e.g. "( 14 %) ↑ world/level.dat (4 KB)"
+pub fn print_transfer(done: usize, total: usize, arrow: &str, path: &str, size: u64) {
+    let progress = (done * 100).checked_div(total).unwrap_or(100);
+
+    // Cut of path at the start to save space
+    let print_path = if path.chars().count() > MAX_PATH_LENGTH {
+        let skip = path.chars().count() - (MAX_PATH_LENGTH - 3);
+        format!("...{}", path.chars().skip(skip).collect::<String>())
+    } else {
+        path.to_string()
+    };
+
+    println!(
+        "({:>3} %) {} {:<width$} ({})",
+        progress, arrow, print_path, format_size(size, DECIMAL), width = MAX_PATH_LENGTH
+    );
+}
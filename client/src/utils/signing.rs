@@ -0,0 +1,80 @@
+use std::process::exit;
+
+use data_encoding::BASE64;
+use mcsync_proto::{info::ServerInfo, signing::fingerprint};
+use paris::{error, info, warn};
+use ring::{rand::SystemRandom, signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey}};
+
+/// Returns a new Ed25519 key pair as base64 encoded PKCS#8 document, the way it's stored in the config.
+pub fn generate_identity() -> String {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+
+    BASE64.encode(pkcs8.as_ref())
+}
+
+pub fn load_identity(pkcs8: &str) -> Option<Ed25519KeyPair> {
+    let pkcs8 = BASE64.decode(pkcs8.as_bytes()).ok()?;
+
+    Ed25519KeyPair::from_pkcs8(&pkcs8).ok()
+}
+
+/// Base64 encoded public key and signature of `message`.
+pub fn sign(identity: &Ed25519KeyPair, message: &[u8]) -> (String, String) {
+    (
+        BASE64.encode(identity.public_key().as_ref()),
+        BASE64.encode(identity.sign(message).as_ref())
+    )
+}
+
+/// Checks an Ed25519 signature. Returns the fingerprint of the signing key if it's valid.
+pub fn verify(public_key: &str, message: &[u8], signature: &str) -> Option<String> {
+    let public_key = BASE64.decode(public_key.as_bytes()).ok()?;
+    let signature = BASE64.decode(signature.as_bytes()).ok()?;
+
+    UnparsedPublicKey::new(&signature::ED25519, &public_key)
+        .verify(message, &signature)
+        .ok()?;
+
+    Some(fingerprint(&public_key))
+}
+
+/// Returns the fingerprint of the server's signing key or None if the server info isn't signed.
+pub fn verify_server_info(server_info: &ServerInfo) -> Result<Option<String>, String> {
+    let (signing_key, signature) = match (&server_info.signing_key, &server_info.signature) {
+        (Some(k), Some(s)) => (k, s),
+        _ => return Ok(None)
+    };
+
+    match verify(signing_key, server_info.signed_message().as_bytes(), signature) {
+        Some(fingerprint) => Ok(Some(fingerprint)),
+        None => Err(String::from("Signature of server info is invalid. It has been altered or forged."))
+    }
+}
+
+/// Shows the server's fingerprint and exits if it doesn't match `expected`.
+pub fn check_fingerprint(server_info: &ServerInfo, expected: Option<&str>) {
+    match (verify_server_info(server_info), expected) {
+        (Err(error), _) => {
+            error!("{}", error);
+            exit(1);
+        },
+        (Ok(Some(actual)), Some(expected)) if !actual.eq_ignore_ascii_case(expected.trim()) => {
+            error!("Fingerprint of this server is {} but you expected {}. Don't use it and tell the server owner!", actual, expected);
+            exit(1);
+        },
+        (Ok(Some(actual)), Some(_)) => {
+            info!("Server fingerprint {} matches.", actual);
+        },
+        (Ok(Some(actual)), None) => {
+            info!("Server fingerprint: {}", actual);
+            info!("Compare it with the output of \"mcsync-server fingerprint\" on the server, e.g. via phone.");
+        },
+        (Ok(None), Some(_)) => {
+            error!("This server info isn't signed, so its fingerprint cannot be checked.");
+            exit(1);
+        },
+        (Ok(None), None) => {
+            warn!("This server info isn't signed. Make sure it really comes from the server owner.");
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::info::ClientInfo;
+
+/// Body of `POST /join` on the enrollment listener.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JoinRequest {
+    pub name: String,
+    pub client_info: ClientInfo,
+
+    /// Lets the client in right away instead of waiting for approval.
+    #[serde(default)]
+    pub invite: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JoinResponse {
+    /// Used to poll for the server info once an admin approved the request.
+    pub token: String
+}
@@ -0,0 +1,143 @@
+//! Crash-safe writes of the files each side keeps (database.json, config.json, .sync).
+
+use std::{
+    fs::{self, File, Metadata, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering}
+};
+
+use paris::error;
+use serde::de::DeserializeOwned;
+
+// Messages go to stderr. Stdout may be reserved for JSON that gets piped into a file.
+
+/// Only complain once per run about files that can't be replaced atomically.
+static WARNED_IN_PLACE: AtomicBool = AtomicBool::new(false);
+
+/// Previous version of a file written by `write_atomic`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, "bak")
+}
+
+/// Keeps a copy of the file as it was before migrating it from `version`. An existing copy wins, since it's the original.
+pub fn keep_before_migration(path: &Path, version: u16) -> io::Result<PathBuf> {
+    let backup = with_suffix(path, &format!("v{}.bak", version));
+    if !backup.exists() {
+        fs::copy(path, &backup)?;
+    }
+
+    Ok(backup)
+}
+
+/// Replaces the file at `path` without ever leaving a half-written file behind: The new contents go into a
+/// temporary file first, which replaces the old one once it's safely on disk. The old version is kept as `.bak`.
+///
+/// New files are created using `mode`, existing ones keep their permissions.
+pub fn write_atomic(path: &Path, contents: &[u8], mode: u32) -> io::Result<()> {
+    write_atomic_with(path, contents, mode, |_, _| ())
+}
+
+/// Like `write_atomic`, but `prepare` gets to adjust the new file before it's written, given the metadata of the old one.
+pub fn write_atomic_with(path: &Path, contents: &[u8], mode: u32, prepare: impl Fn(&File, &Metadata)) -> io::Result<()> {
+    if path.exists() {
+        // A copy rather than a hard link, since the fallback below overwrites the file in place.
+        fs::copy(path, backup_path(path))?;
+    }
+
+    replace(path, contents, mode, &prepare)
+}
+
+/// Reads a JSON file written by `write_atomic` along with its raw contents.
+///
+/// If the file is broken (e.g. an older version crashed while writing it), the backup takes its place
+/// and the broken file is kept as `.corrupt`. Returns `None` if there is neither a file nor a backup.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<(T, String)>, String> {
+    read_json_with(path, |_, _| ())
+}
+
+/// Like `read_json`, but a restored file is written using `prepare` (see `write_atomic_with`).
+pub fn read_json_with<T: DeserializeOwned>(path: &Path, prepare: impl Fn(&File, &Metadata)) -> Result<Option<(T, String)>, String> {
+    // Missing and empty files are fine unless there is a backup. Then we probably crashed while writing.
+    let (problem, broken) = match fs::read_to_string(path) {
+        Ok(contents) if contents.trim().is_empty() => (String::from("empty"), false),
+        Ok(contents) => match serde_json::from_str(&contents) {
+            Ok(data) => return Ok(Some((data, contents))),
+            Err(error) => (format!("invalid ({})", error), true)
+        },
+        Err(error) if error.kind() == io::ErrorKind::NotFound => (String::from("missing"), false),
+        Err(error) => return Err(format!("Couldn't read {}: {}", path.display(), error))
+    };
+
+    let backup = backup_path(path);
+    let restored = fs::read_to_string(&backup).ok()
+        .and_then(|contents| serde_json::from_str::<T>(&contents).ok().map(|data| (data, contents)));
+
+    match restored {
+        Some((data, contents)) => {
+            error!("{} is {}. Restored the previous version from {}.", path.display(), problem, backup.display());
+
+            // Keep the broken file for inspection, but don't let it replace the backup.
+            if path.exists() {
+                let _ = fs::copy(path, with_suffix(path, "corrupt"));
+            }
+
+            let mode = fs::metadata(&backup).map(|m| m.permissions().mode()).unwrap_or(0o600);
+            replace(path, contents.as_bytes(), mode, &prepare)
+                .map_err(|e| format!("Couldn't restore {}: {}", path.display(), e))?;
+
+            Ok(Some((data, contents)))
+        },
+        None if broken => Err(format!("{} is {} and there is no usable backup.", path.display(), problem)),
+        None => Ok(None)
+    }
+}
+
+fn replace(path: &Path, contents: &[u8], mode: u32, prepare: &impl Fn(&File, &Metadata)) -> io::Result<()> {
+    let metadata = fs::metadata(path).ok();
+    let mode = metadata.as_ref().map(|m| m.permissions().mode()).unwrap_or(mode);
+    let temp = with_suffix(path, "tmp");
+
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(mode).open(&temp)?;
+
+    if let Some(metadata) = &metadata {
+        prepare(&file, metadata);
+    }
+
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    if let Err(error) = fs::rename(&temp, path) {
+        // Files that are bind-mounted into a container on their own cannot be replaced, only overwritten.
+        let _ = fs::remove_file(&temp);
+        if !WARNED_IN_PLACE.swap(true, Ordering::Relaxed) {
+            error!("Cannot replace {} ({}). Overwrite it instead. Mount its directory to make writes crash-safe.", path.display(), error);
+        }
+
+        let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        return Ok(());
+    }
+
+    // The rename itself is only durable once the directory hit the disk as well.
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+/// `path` with `.<suffix>` appended, e.g. database.json.bak.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+
+    PathBuf::from(name)
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+/// Version of `ServerInfo` this build writes and the newest one it can read.
+pub const SERVER_INFO_VERSION: u16 = 1;
+
+/// Version of `ClientInfo` this build writes and the newest one it can read.
+pub const CLIENT_INFO_VERSION: u16 = 1;
+
+/// Everything a client needs to connect. Clients import it as .mcss file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub version: u16,
+    pub endpoint: String,
+    pub public_key: String,
+    pub psk: String,
+    pub tool_subnet: String,
+    pub user_subnet: String,
+    pub ipv4_address: String,
+    pub dns: String,
+
+    /// Public part of the server's Ed25519 identity. Missing in files of older servers.
+    #[serde(default)]
+    pub signing_key: Option<String>,
+
+    /// Covers everything above, see `ServerInfo::signed_message`.
+    #[serde(default)]
+    pub signature: Option<String>
+}
+
+impl ServerInfo {
+    pub fn is_supported(&self) -> bool {
+        self.version <= SERVER_INFO_VERSION
+    }
+
+    /// What the server signs and clients verify. Don't change it without bumping the version.
+    pub fn signed_message(&self) -> String {
+        [
+            "mcsync-server-info",
+            &self.version.to_string(),
+            &self.endpoint,
+            &self.public_key,
+            &self.psk,
+            &self.tool_subnet,
+            &self.user_subnet,
+            &self.ipv4_address,
+            &self.dns
+        ].join("\n")
+    }
+}
+
+/// What a client hands over in order to be accepted. Saved as .mcsc file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub version: u16,
+    pub wireguard_pub: String,
+
+    /// Public part of the client's Ed25519 identity. Older clients don't sign their client info.
+    #[serde(default)]
+    pub signing_key: Option<String>,
+
+    #[serde(default)]
+    pub signature: Option<String>
+}
+
+impl ClientInfo {
+    pub fn is_supported(&self) -> bool {
+        self.version <= CLIENT_INFO_VERSION
+    }
+
+    /// What the client signs and servers verify. Don't change it without bumping the version.
+    pub fn signed_message(&self) -> String {
+        format!("mcsync-client-info\n{}\n{}", self.version, self.wireguard_pub)
+    }
+}
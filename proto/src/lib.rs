@@ -0,0 +1,25 @@
+//! Types that travel between client and server, either as HTTP bodies or as files (.mcss, .mcsc).
+//!
+//! Both binaries depend on this crate, so they can't drift apart anymore. Still, members don't update
+//! at the same time, so every change has to stay compatible with older clients and servers:
+//!
+//! * Never rename or remove a field. Serde would silently ignore it on the other side.
+//! * New fields are `Option` or carry `#[serde(default)]`, so older files and peers can still be read.
+//! * Unknown fields are ignored, so newer peers can still be read as well.
+//! * If a format changes in a way older readers can't handle, bump its version constant.
+//!   Readers refuse versions newer than their own.
+//!
+//! Files that only one side keeps (database.json, config.json, .sync) are upgraded instead, using `migration`.
+//!
+//! Output both sides have to produce identically, like fingerprints and timestamps users compare, lives here as well.
+//! So does code both sides need in the same way, like writing their files crash-safe.
+
+pub mod enrollment;
+pub mod file;
+pub mod info;
+pub mod migration;
+pub mod signing;
+pub mod status;
+pub mod sync;
+pub mod time;
+pub mod version;
@@ -0,0 +1,58 @@
+use serde_json::{Map, Value};
+
+/// Upgrades a file by one version. It gets the file as JSON object and doesn't have to touch `version`.
+///
+/// Steps never change once released. If a later version needs something else, it gets a step of its own.
+pub type Step = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// Version a file has after running all `steps`. Files start at version 1.
+pub const fn latest(steps: &[Step]) -> u16 {
+    steps.len() as u16 + 1
+}
+
+/// Upgrades `value` to the latest version, one step at a time. `steps[0]` upgrades from version 1 to 2 and so on.
+///
+/// Returns the version the file had before, or `None` if it already was up to date.
+/// Files of newer versions are refused, since we can't know what we'd lose by writing them.
+pub fn migrate(value: &mut Value, steps: &[Step]) -> Result<Option<u16>, String> {
+    let latest = latest(steps);
+
+    let object = value.as_object_mut().ok_or_else(|| String::from("Not a JSON object"))?;
+    let version = match object.get("version").and_then(Value::as_u64) {
+        Some(version) if version > 0 => version,
+        _ => return Err(String::from("Missing or invalid version"))
+    };
+
+    if version > latest as u64 {
+        return Err(format!("Version {} has been created by a newer version of mcsync (supports up to {}). Please upgrade.", version, latest));
+    }
+
+    if version == latest as u64 {
+        return Ok(None);
+    }
+
+    for (index, step) in steps.iter().enumerate().skip(version as usize - 1) {
+        step(object).map_err(|e| format!("Couldn't migrate from version {} to {}: {}", index + 1, index + 2, e))?;
+        object.insert(String::from("version"), Value::from(index + 2));
+    }
+
+    Ok(Some(version as u16))
+}
+
+/// Sets `key` unless it's already there. Most steps just add fields older versions didn't know.
+pub fn fill(object: &mut Map<String, Value>, key: &str, value: Value) {
+    object.entry(key).or_insert(value);
+}
+
+/// Every object within the array at `key`. Missing arrays are created empty.
+pub fn entries<'a>(object: &'a mut Map<String, Value>, key: &str) -> Result<impl Iterator<Item = &'a mut Map<String, Value>>, String> {
+    let array = object.entry(key).or_insert_with(|| Value::Array(vec![]))
+        .as_array_mut()
+        .ok_or_else(|| format!("{} is not an array", key))?;
+
+    if array.iter().any(|entry| !entry.is_object()) {
+        return Err(format!("{} contains something that's not an object", key));
+    }
+
+    Ok(array.iter_mut().filter_map(Value::as_object_mut))
+}
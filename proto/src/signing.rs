@@ -0,0 +1,12 @@
+use sha2::{Digest, Sha256};
+
+/// Short enough to be compared over the phone, e.g. "3F2A-91C0-7D4E-B815-02AA".
+///
+/// Users compare what the client and the server print by eye, so both have to use this one.
+pub fn fingerprint(public_key: &[u8]) -> String {
+    Sha256::digest(public_key)[..10]
+        .chunks(2)
+        .map(|pair| format!("{:02X}{:02X}", pair[0], pair[1]))
+        .collect::<Vec<String>>()
+        .join("-")
+}
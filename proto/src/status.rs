@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// Body of `GET /status`. Everything `mcsync status` shows.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatusResponse {
+    /// Version of mcsync running on the backend.
+    pub version: String,
+    pub servers: Vec<StatusGameServer>,
+    pub members: Vec<StatusMember>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatusGameServer {
+    pub name: String,
+
+    /// Name of the member that is hosting right now.
+    pub host: Option<String>,
+
+    /// Minecraft version and player count are only known once it has been hosted.
+    pub version: Option<String>,
+    pub online_players: u32,
+    pub max_players: u32,
+
+    pub last_host: String,
+    pub last_hosted: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatusMember {
+    pub name: String,
+    pub online: bool,
+    pub last_seen: u64
+}
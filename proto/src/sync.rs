@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CreateServer {
+    pub server_name: String,
+
+    /// Script (without extension) or .jar-file relative to the game server's root.
+    #[serde(default)]
+    pub start_file: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CreateServerResponse {
+    pub server_uuid: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileHash {
+    pub id: u32,
+    pub size: u64,
+    pub path: String,
+    pub hash: String
+}
+
+/// List of all files (path, size & SHA-256) that belong to the latest generation of a game server.
+/// Body of `GET /server/{id}/manifest` and stored as manifest.json next to each generation on the backend.
+///
+/// The generation is the UNIX timestamp of the last successful sync. Clients have to know
+/// at least this generation in order to push new changes.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub generation: u64,
+    pub files: Vec<FileHash>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeltaClient {
+    pub files: Vec<FileHash>,
+
+    /// Generation the client pulled last.
+    pub last_sync: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DeltaServer {
+    pub new: Vec<FileHash>,
+    pub modified: Vec<FileHash>,
+    pub removed: Vec<FileHash>,
+
+    /// Only set if there was nothing to transfer and the delta has been committed right away.
+    #[serde(default)]
+    pub generation: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ServerEntry {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    pub start_file: String,
+    pub generation: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransferResponse {
+    /// Amount of files that still need to be transferred before the new generation goes live.
+    pub remaining: usize,
+
+    /// Set once the last file arrived and the new generation is live.
+    pub generation: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LeaseResponse {
+    /// Name of the hosting client.
+    pub holder: String,
+    pub acquired: u64,
+
+    /// The lease is lost if there is no heartbeat until then.
+    pub expires: u64
+}
@@ -0,0 +1,42 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Turns a UNIX timestamp into something like "2 hours ago".
+pub fn format_ago(timestamp: u64) -> String {
+    let seconds = now().saturating_sub(timestamp);
+
+    if seconds < 60 {
+        return String::from("just now");
+    }
+
+    format!("{} ago", format_duration(seconds))
+}
+
+/// Turns a future UNIX timestamp into something like "in 2 hours".
+pub fn format_in(timestamp: u64) -> String {
+    let seconds = timestamp.saturating_sub(now());
+
+    if seconds < 60 {
+        return String::from("in less than a minute");
+    }
+
+    format!("in {}", format_duration(seconds))
+}
+
+/// Turns an amount of seconds into something like "2 hours", rounded down to the largest unit.
+pub fn format_duration(seconds: u64) -> String {
+    let (amount, unit) = match seconds {
+        0..=59 => (seconds, "second"),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        86400..=604799 => (seconds / 86400, "day"),
+        604800..=2629799 => (seconds / 604800, "week"),
+        2629800..=31557599 => (seconds / 2629800, "month"),
+        _ => (seconds / 31557600, "year")
+    };
+
+    format!("{} {}{}", amount, unit, if amount == 1 { "" } else { "s" })
+}
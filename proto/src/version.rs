@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Version of the HTTP API between clients and the backend. Bump it once an endpoint changes in a way
+/// older peers can't handle, and raise `MIN_PROTOCOL_VERSION` once the old way is gone.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Oldest protocol this build still speaks.
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+/// Optional parts of the API. Clients check for them before using one, so backends may lag behind.
+pub mod features {
+    /// Pushing and pulling game servers (`/server/...`).
+    pub const SYNC: &str = "sync";
+
+    /// Only one member hosts a game server at a time (`/server/{id}/lease`).
+    pub const LEASES: &str = "leases";
+
+    /// `GET /status`
+    pub const STATUS: &str = "status";
+
+    /// Self-service enrollment and invites on the enrollment listener.
+    pub const ENROLLMENT: &str = "enrollment";
+}
+
+/// Body of `GET /version`. Clients fetch it before talking any further.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VersionResponse {
+    /// Version of mcsync running on the backend.
+    pub version: String,
+    pub protocol_min: u16,
+    pub protocol_max: u16,
+
+    #[serde(default)]
+    pub features: Vec<String>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compatibility {
+    Compatible,
+
+    /// The other side dropped every protocol we speak.
+    UpgradeUs,
+
+    /// The other side doesn't speak any of our protocols yet.
+    UpgradeThem
+}
+
+impl VersionResponse {
+    /// Whether this build can talk to the peer that sent this response.
+    pub fn compatibility(&self) -> Compatibility {
+        if self.protocol_min > PROTOCOL_VERSION {
+            Compatibility::UpgradeUs
+        } else if self.protocol_max < MIN_PROTOCOL_VERSION {
+            Compatibility::UpgradeThem
+        } else {
+            Compatibility::Compatible
+        }
+    }
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
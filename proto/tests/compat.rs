@@ -0,0 +1,151 @@
+use mcsync_proto::{
+    enrollment::JoinRequest,
+    info::{ClientInfo, ServerInfo, CLIENT_INFO_VERSION, SERVER_INFO_VERSION},
+    status::StatusResponse,
+    sync::{DeltaServer, FileHash, LeaseResponse, Manifest},
+    version::{Compatibility, VersionResponse, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION}
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+fn round_trip<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(value: T) {
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value);
+}
+
+fn server_info() -> ServerInfo {
+    ServerInfo {
+        version: SERVER_INFO_VERSION,
+        endpoint: String::from("example.com:51820"),
+        public_key: String::from("VZBslaLy/AXCqk0rXq8Ip/+p7a/RyrG+H/WQ9ZeV8x8="),
+        psk: String::from("Hx4EUfnSED/J409OEKw3jiZQUHK3KsfR2VfgQHYaJqs="),
+        tool_subnet: String::from("192.168.11.0/24"),
+        user_subnet: String::from("192.168.10.0/24"),
+        ipv4_address: String::from("192.168.10.3"),
+        dns: String::from("192.168.11.5"),
+        signing_key: Some(String::from("mT2VqJ9xYk3n8Rw0cB1sE4dG7hL5pZ6aU0iO2fK8jN4=")),
+        signature: Some(String::from("c2lnbmF0dXJl"))
+    }
+}
+
+fn file(id: u32, path: &str) -> FileHash {
+    FileHash { id, size: 42, path: path.to_string(), hash: String::from("ab12") }
+}
+
+#[test]
+fn types_survive_round_trip() {
+    round_trip(server_info());
+    round_trip(JoinRequest {
+        name: String::from("Joe Doe"),
+        client_info: ClientInfo {
+            version: CLIENT_INFO_VERSION,
+            wireguard_pub: String::from("VZBslaLy/AXCqk0rXq8Ip/+p7a/RyrG+H/WQ9ZeV8x8="),
+            signing_key: None,
+            signature: None
+        },
+        invite: Some(String::from("deadbeef"))
+    });
+    round_trip(Manifest { generation: 1660000000, files: vec![file(0, "world/level.dat"), file(1, "server.properties")] });
+    round_trip(DeltaServer { new: vec![file(0, "a")], modified: vec![], removed: vec![file(3, "b")], generation: None });
+    round_trip(LeaseResponse { holder: String::from("Joe Doe"), acquired: 1, expires: 91 });
+}
+
+/// Field names are the contract with older peers. Renaming one breaks them silently.
+#[test]
+fn field_names_are_stable() {
+    let json = serde_json::to_value(server_info()).unwrap();
+    let mut keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+    keys.sort_unstable();
+
+    assert_eq!(keys, [
+        "dns", "endpoint", "ipv4_address", "psk", "public_key", "signature", "signing_key", "tool_subnet", "user_subnet", "version"
+    ]);
+}
+
+/// Files of servers before signing was introduced have to stay importable.
+#[test]
+fn reads_unsigned_server_info() {
+    let mut json = serde_json::to_value(server_info()).unwrap();
+    let object = json.as_object_mut().unwrap();
+    object.remove("signing_key");
+    object.remove("signature");
+
+    let parsed: ServerInfo = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.signing_key, None);
+    assert_eq!(parsed.signature, None);
+    assert!(parsed.is_supported());
+}
+
+/// Newer peers may add fields, older ones (like the very first client info with "ssh_pub") may have more.
+#[test]
+fn ignores_unknown_fields() {
+    let parsed: ClientInfo = serde_json::from_value(json!({
+        "version": 1,
+        "wireguard_pub": "VZBslaLy/AXCqk0rXq8Ip/+p7a/RyrG+H/WQ9ZeV8x8=",
+        "ssh_pub": "ssh-ed25519 AAAA"
+    })).unwrap();
+    assert_eq!(parsed.signing_key, None);
+
+    let mut json = serde_json::to_value(server_info()).unwrap();
+    json["from_the_future"] = Value::from(true);
+    assert_eq!(serde_json::from_value::<ServerInfo>(json).unwrap(), server_info());
+}
+
+#[test]
+fn negotiates_protocol() {
+    let version = |protocol_min, protocol_max| VersionResponse {
+        version: String::from("0.1.0"),
+        protocol_min,
+        protocol_max,
+        features: vec![String::from("sync")]
+    };
+
+    assert_eq!(version(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION).compatibility(), Compatibility::Compatible);
+    assert_eq!(version(PROTOCOL_VERSION + 1, PROTOCOL_VERSION + 1).compatibility(), Compatibility::UpgradeUs);
+    assert_eq!(version(0, MIN_PROTOCOL_VERSION - 1).compatibility(), Compatibility::UpgradeThem);
+
+    // Older backends may not list any features.
+    let parsed: VersionResponse = serde_json::from_value(json!({ "version": "0.1.0", "protocol_min": 1, "protocol_max": 1 })).unwrap();
+    assert!(!parsed.supports("sync"));
+    assert!(version(1, 1).supports("sync"));
+}
+
+#[test]
+fn refuses_newer_versions() {
+    let mut info = server_info();
+    info.version = SERVER_INFO_VERSION + 1;
+    assert!(!info.is_supported());
+}
+
+/// Clients and servers sign and verify this exact string. Changing it invalidates every signature out there.
+#[test]
+fn signed_message_is_stable() {
+    let client_info = ClientInfo {
+        version: 1,
+        wireguard_pub: String::from("key"),
+        signing_key: None,
+        signature: Some(String::from("ignored"))
+    };
+    assert_eq!(client_info.signed_message(), "mcsync-client-info\n1\nkey");
+
+    assert_eq!(server_info().signed_message(), [
+        "mcsync-server-info", "1", "example.com:51820", "VZBslaLy/AXCqk0rXq8Ip/+p7a/RyrG+H/WQ9ZeV8x8=",
+        "Hx4EUfnSED/J409OEKw3jiZQUHK3KsfR2VfgQHYaJqs=", "192.168.11.0/24", "192.168.10.0/24", "192.168.10.3", "192.168.11.5"
+    ].join("\n"));
+}
+
+#[test]
+fn optional_fields_have_defaults() {
+    let status: StatusResponse = serde_json::from_value(json!({
+        "version": "0.1.0",
+        "servers": [{
+            "name": "survival", "host": null, "version": null, "online_players": 0, "max_players": 0,
+            "last_host": "", "last_hosted": 0
+        }],
+        "members": []
+    })).unwrap();
+    assert_eq!(status.servers[0].host, None);
+
+    let delta: DeltaServer = serde_json::from_value(json!({ "new": [], "modified": [], "removed": [] })).unwrap();
+    assert_eq!(delta.generation, None);
+}
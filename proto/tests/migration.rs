@@ -0,0 +1,60 @@
+use mcsync_proto::migration::{entries, fill, latest, migrate, Step};
+use serde_json::{json, Map, Value};
+
+fn add_name(object: &mut Map<String, Value>) -> Result<(), String> {
+    fill(object, "name", Value::from("unnamed"));
+    Ok(())
+}
+
+fn rename_players(object: &mut Map<String, Value>) -> Result<(), String> {
+    for player in entries(object, "players")? {
+        if let Some(name) = player.remove("nick") {
+            player.insert(String::from("name"), name);
+        }
+    }
+
+    Ok(())
+}
+
+const STEPS: &[Step] = &[add_name, rename_players];
+
+#[test]
+fn upgrades_step_by_step() {
+    assert_eq!(latest(STEPS), 3);
+
+    let mut value = json!({ "version": 1, "players": [{ "nick": "Joe" }] });
+    assert_eq!(migrate(&mut value, STEPS), Ok(Some(1)));
+    assert_eq!(value, json!({ "version": 3, "name": "unnamed", "players": [{ "name": "Joe" }] }));
+
+    // Only the missing steps run.
+    let mut value = json!({ "version": 2, "players": [] });
+    assert_eq!(migrate(&mut value, STEPS), Ok(Some(2)));
+    assert_eq!(value, json!({ "version": 3, "players": [] }));
+}
+
+#[test]
+fn keeps_current_files() {
+    let mut value = json!({ "version": 3, "name": "survival" });
+    assert_eq!(migrate(&mut value, STEPS), Ok(None));
+    assert_eq!(value, json!({ "version": 3, "name": "survival" }));
+}
+
+#[test]
+fn refuses_newer_and_broken_files() {
+    assert!(migrate(&mut json!({ "version": 4 }), STEPS).is_err());
+    assert!(migrate(&mut json!({ "version": 0 }), STEPS).is_err());
+    assert!(migrate(&mut json!({ "name": "survival" }), STEPS).is_err());
+    assert!(migrate(&mut json!([1, 2]), STEPS).is_err());
+    assert!(migrate(&mut json!({ "version": 2, "players": [1] }), STEPS).is_err());
+}
+
+#[test]
+fn fill_keeps_existing_values() {
+    let mut object = Map::new();
+    object.insert(String::from("name"), Value::from("survival"));
+
+    fill(&mut object, "name", Value::from("unnamed"));
+    fill(&mut object, "share", Value::from(true));
+
+    assert_eq!(Value::Object(object), json!({ "name": "survival", "share": true }));
+}
@@ -0,0 +1,7 @@
+use mcsync_proto::signing::fingerprint;
+
+#[test]
+fn fingerprint_is_the_start_of_the_sha256_hash() {
+    // SHA-256 of 32 zero bytes starts with 66687aadf862bd776c8f.
+    assert_eq!(fingerprint(&[0; 32]), "6668-7AAD-F862-BD77-6C8F");
+}
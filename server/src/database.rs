@@ -1,15 +1,29 @@
-use std::{fs::File, io::{Read, Write}, time::{SystemTime, UNIX_EPOCH}, vec, path::Path, process::exit};
+use std::{fmt, time::{Duration, SystemTime, UNIX_EPOCH}, vec, process::exit, sync::{Arc, Mutex}};
 
-use paris::{error, warn};
+use clap::ValueEnum;
+use mcsync_proto::time::now;
+
+use paris::{error, info, warn};
 use serde::{Deserialize, Serialize};
 
-use crate::env;
+use crate::{signing::Identity, storage::{self, merge, migration::DATABASE_VERSION, Storage, StoreError}};
+
+/// Hosts have to renew their lease within this amount of seconds. Otherwise someone else may take over.
+pub const LEASE_TIMEOUT: u64 = 90;
+
+/// How often `Database::flush` merges and tries again if another process stored something at the same time.
+const FLUSH_ATTEMPTS: usize = 3;
+
+/// One database handle shared by everything that runs inside the backend process.
+pub type SharedDatabase = Arc<Mutex<Database>>;
 
-#[derive(Clone)]
 pub struct Database {
-    path: String,
+    storage: Box<dyn Storage>,
+
+    /// What the storage held when we loaded or stored it last. Changes of other processes are merged against it.
+    stored: DatabaseFormat,
 
-    /// Stores the fingerprint of the latest written database to prevent flushing
+    /// Stores the fingerprint of the latest stored database to prevent flushing
     /// the same contents all the time.
     fingerprint: String,
     data: DatabaseFormat
@@ -17,22 +31,144 @@ pub struct Database {
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct DatabaseFormat {
-    version: u16,
-    keys: DatabaseKeys,
-    synced: Vec<DatabaseSynced>,
-    client: Vec<DatabaseClient>
+    pub(crate) version: u16,
+    pub(crate) keys: DatabaseKeys,
+    pub(crate) synced: Vec<DatabaseSynced>,
+    pub(crate) client: Vec<DatabaseClient>,
+
+    /// Join requests that came in over the enrollment endpoint.
+    pub(crate) pending: Vec<DatabasePending>,
+
+    /// Tokens that let someone join without waiting for approval.
+    pub(crate) invites: Vec<DatabaseInvite>,
+
+    /// Named sets of clients. Roles can be granted to a whole group at once.
+    pub(crate) groups: Vec<DatabaseGroup>
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct DatabaseKeys {
-    wg_private: String
+    wg_private: String,
+
+    /// Signs server infos, so clients can tell they really come from us. See `signing::Identity`.
+    signing_private: String
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct DatabaseSynced {
     pub(crate) id: String,
     pub(crate) name: String,
-    pub(crate) share: bool
+
+    /// Name of the client that created this game server. Empty if unknown or the owner has been removed.
+    pub(crate) owner: String,
+
+    /// Role of everyone without a grant of their own or of one of their groups.
+    pub(crate) default_role: Role,
+
+    /// Roles of single clients and groups. There is at most one grant per client or group.
+    pub(crate) grants: Vec<DatabaseGrant>,
+
+    /// Script (without extension) or .jar-file relative to the game server's root.
+    pub(crate) start_file: String,
+
+    pub(crate) created: u64,
+
+    /// Set while someone is hosting this game server.
+    pub(crate) lease: Option<DatabaseLease>,
+
+    /// Name of the client that hosted this game server most recently.
+    pub(crate) last_host: String,
+
+    /// Last time this game server was known to be hosted.
+    pub(crate) last_hosted: u64,
+
+    /// What the Minecraft server reported about itself the last time it was hosted.
+    pub(crate) last_status: Option<DatabaseServerStatus>
+}
+
+/// What a client may do with a game server. Each role includes the ones before it.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Doesn't even see the game server.
+    None,
+
+    /// May pull the game server and play on it.
+    Player,
+
+    /// May host it and push changes.
+    Host,
+
+    /// Same as host for now. The client that created a game server is its owner until they get removed.
+    Owner
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Role::None => "none",
+            Role::Player => "player",
+            Role::Host => "host",
+            Role::Owner => "owner"
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// Who a grant applies to. Stored as `{ "client": "Joe Doe" }` or `{ "group": "friends" }`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Subject {
+    Client(String),
+    Group(String)
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::Client(name) => write!(f, "client {}", name),
+            Subject::Group(name) => write!(f, "group {}", name)
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DatabaseGrant {
+    #[serde(flatten)]
+    pub(crate) subject: Subject,
+    pub(crate) role: Role
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DatabaseGroup {
+    pub(crate) name: String,
+
+    /// Names of clients.
+    pub(crate) members: Vec<String>
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DatabaseServerStatus {
+    pub(crate) version: String,
+    pub(crate) protocol: i32,
+    pub(crate) max_players: u32,
+    pub(crate) online_players: u32
+}
+
+/// Only the holder of a lease may run and push a game server.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DatabaseLease {
+    /// IPv4 address of the hosting client.
+    pub(crate) holder: String,
+    pub(crate) acquired: u64,
+    pub(crate) heartbeat: u64
+}
+
+impl DatabaseLease {
+    pub fn is_expired(&self) -> bool {
+        self.heartbeat + LEASE_TIMEOUT < now()
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -41,75 +177,148 @@ pub struct DatabaseClient {
     pub(crate) ipv4_address: String,
     pub(crate) last_seen: u64,
     pub(crate) wg_public_key: String,
-    pub(crate) wg_psk: String
+    pub(crate) wg_psk: String,
+
+    /// True while the client's tunnel is up. Maintained by `presence::Presence`.
+    pub(crate) online: bool
+}
+
+/// Someone who asked to join using `mcsync join`. Stays until the client picked up its server info, got denied
+/// or nobody approved it in time.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DatabasePending {
+    pub(crate) name: String,
+    pub(crate) wg_public_key: String,
+
+    /// Secret the client uses to poll for its server info.
+    pub(crate) token: String,
+    pub(crate) requested: u64,
+
+    /// IP address the request came from.
+    pub(crate) address: String,
+
+    /// Fingerprint of the client's identity key. Empty if the client info wasn't signed.
+    pub(crate) fingerprint: String,
+
+    /// Set once an admin approved the request.
+    pub(crate) server_info: Option<serde_json::Value>
+}
+
+/// Created by `mcsync-server invite`. Gone once it's used up, expired or revoked.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DatabaseInvite {
+    pub(crate) token: String,
+    pub(crate) uses_left: u32,
+    pub(crate) created: u64,
+    pub(crate) expires: u64
+}
+
+impl DatabaseInvite {
+    pub fn is_expired(&self) -> bool {
+        self.expires <= now()
+    }
 }
 
-/// It's not a real database. We just dump everything into a .json file to remember various things.
-/// A full-featured database is a bit overpowered. If things get too complicated I may migrate to SQLite.
-/// 
-/// This implementation shouldn't be very slow since we work in-memory and flush on exit.
+/// All state of the backend. Every process (the backend and each CLI command) holds exactly one `Database`
+/// and works on it in-memory. Changes are handed to the `Storage` on flush, which is a .json file by default
+/// or SQLite if DATABASE_BACKEND=sqlite.
 impl Database {
     pub fn new() -> Self {
-        let path = env::get_database_path();
+        Self::open(storage::open())
+    }
 
-        if !Path::new(&path).exists() {
-            match File::create(&path) {
-                Ok(_) => {},
-                Err(error) => {
-                    error!("Couldn't create database file at {}: {}", &path, error);
-                    exit(1);
-                }
+    /// Loads the database from `storage` or creates a new one, keys included.
+    pub fn open(mut storage: Box<dyn Storage>) -> Self {
+        let (data, fingerprint) = match storage.load() {
+            Ok(Some(data)) => {
+                let fingerprint = fingerprint(&data);
+                (data, fingerprint)
+            },
+            Ok(None) => {
+                let private_key = wireguard_keys::Privkey::generate();
+
+                let data = DatabaseFormat {
+                    version: DATABASE_VERSION,
+                    keys: DatabaseKeys {
+                        wg_private: private_key.to_base64(),
+                        signing_private: Identity::generate()
+                    },
+                    client: vec![],
+                    synced: vec![],
+                    pending: vec![],
+                    invites: vec![],
+                    groups: vec![]
+                };
+
+                // Nothing stored yet, so the first flush has to write.
+                (data, String::new())
+            },
+            Err(error) => {
+                error!("Couldn't load database from {}: {}", storage.location(), error);
+                exit(1);
             }
-        }
+        };
 
-        let data = match File::open(&path) {
-            Ok(mut data) => {
-                let mut contents = String::new();
-                let read_operation = data.read_to_string(&mut contents);
+        let mut database = Self { storage, stored: data.clone(), data, fingerprint };
 
-                if read_operation.is_err() {
-                    error!("Couldn't read file: {}", read_operation.unwrap_err());
-                    std::process::exit(1);
-                }
+        // Databases created before server infos got signed don't have a signing key yet.
+        if database.data.keys.signing_private.is_empty() {
+            database.data.keys.signing_private = Identity::generate();
+        }
 
-                let size = read_operation.unwrap();
+        if database.flush().is_none() {
+            error!("Couldn't write database to {}. See previous errors.", database.storage.location());
+            exit(1);
+        }
 
-                // Empty file
-                if size == 0 {
-                    let private_key = wireguard_keys::Privkey::generate();
+        database
+    }
 
-                    DatabaseFormat {
-                        version: 1,
-                        keys: DatabaseKeys {
-                            wg_private: private_key.to_base64()
-                        },
-                        client: vec![],
-                        synced: vec![]
+    /// Picks up changes that another process (e.g. `mcsync-server remove`) stored in the meantime.
+    /// Our own changes that haven't been stored yet are kept, unless they touch something that is gone now.
+    /// Returns true if anything has been picked up.
+    pub fn reload_if_changed(&mut self) -> bool {
+        match self.storage.load_if_changed() {
+            Ok(Some(theirs)) => {
+                self.data = match merge::merge(&self.stored, &self.data, &theirs) {
+                    Ok(merged) => merged,
+                    Err(error) => {
+                        // Theirs win. Whatever we'd lose is set again with the next request.
+                        warn!("{}", error);
+                        theirs.clone()
                     }
-                } else {
-                    match serde_json::from_str::<DatabaseFormat>(&contents) {
-                        Ok(o) => o,
-                        Err(error) => {
-                            error!("Invalid json syntax: {}", error);
-                            std::process::exit(1);
-                        }
-                    }    
-                }
-                
+                };
+
+                self.fingerprint = fingerprint(&theirs);
+                self.stored = theirs;
+
+                true
             },
+            Ok(None) => false,
             Err(error) => {
-                error!("Couldn't open database file: {}", error);
-                std::process::exit(1);
+                // Probably caught the other process while writing. Try again next time.
+                warn!("{}", error);
+                false
             }
-        };
+        }
+    }
 
-        Self { path, data, fingerprint: String::new() }
+    /// Replaces everything, keys included. Used to move an existing database into another storage.
+    pub fn import(&mut self, data: DatabaseFormat) {
+        self.data = data;
+    }
+
+    /// True as long as nobody joined and no game server has been created.
+    pub fn is_empty(&self) -> bool {
+        self.data.client.is_empty() && self.data.synced.is_empty() && self.data.pending.is_empty() && self.data.invites.is_empty()
     }
 
     pub fn new_client(&mut self, client: DatabaseClient) {
         self.data.client.push(client);
     }
 
+    /// Also takes away everything the client has been granted and what they own, so nobody who gets their name later
+    /// inherits it.
     pub fn remove_client(&mut self, name: &str) -> Option<DatabaseClient> {
         let position = self.data.client.iter().position(|c| c.name == name);
         let client = self.get_client_by_name(name);
@@ -119,6 +328,24 @@ impl Database {
                 let client_clone = client.unwrap().to_owned();
                 self.data.client.remove(pos);
 
+                let subject = Subject::Client(name.to_string());
+                for sync in self.data.synced.iter_mut() {
+                    sync.grants.retain(|g| g.subject != subject);
+
+                    if sync.owner == name {
+                        sync.owner.clear();
+                    }
+
+                    if sync.last_host == name {
+                        sync.last_host.clear();
+                    }
+                }
+
+                for group in self.data.groups.iter_mut() {
+                    group.members.retain(|m| m != name);
+                }
+                self.data.groups.retain(|g| !g.members.is_empty());
+
                 Some(client_clone)
             },
             None => {
@@ -127,6 +354,48 @@ impl Database {
         }
     }
 
+    /// Renames a client everywhere it's referenced. Fails if `new_name` is taken.
+    pub fn rename_client(&mut self, name: &str, new_name: &str) -> Option<()> {
+        if self.get_client_by_name(new_name).is_some() || self.owns_syncs(new_name) {
+            return None;
+        }
+
+        let client = self.data.client.iter_mut().find(|c| c.name == name)?;
+        client.name = new_name.to_string();
+
+        for sync in self.data.synced.iter_mut() {
+            if sync.owner == name {
+                sync.owner = new_name.to_string();
+            }
+
+            if sync.last_host == name {
+                sync.last_host = new_name.to_string();
+            }
+
+            for grant in sync.grants.iter_mut() {
+                if grant.subject == Subject::Client(name.to_string()) {
+                    grant.subject = Subject::Client(new_name.to_string());
+                }
+            }
+        }
+
+        for group in self.data.groups.iter_mut() {
+            for member in group.members.iter_mut() {
+                if member == name {
+                    *member = new_name.to_string();
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    pub fn set_client_psk(&mut self, name: &str, wg_psk: &str) {
+        if let Some(client) = self.data.client.iter_mut().find(|c| c.name == name) {
+            client.wg_psk = wg_psk.to_string();
+        }
+    }
+
     /// Call once you have seen the client.
     pub fn seen_client(&mut self, ip: &str) {
         match self.data.client.iter().position(|c| c.ipv4_address == ip) {
@@ -142,6 +411,22 @@ impl Database {
         }
     } 
 
+    /// Updates the presence of the client with the given WireGuard key. `last_seen` never moves backwards.
+    /// Returns true if anything changed.
+    pub fn set_presence(&mut self, wg_public_key: &str, seen: u64, online: bool) -> bool {
+        match self.data.client.iter_mut().find(|c| c.wg_public_key == wg_public_key) {
+            Some(client) => {
+                let changed = client.online != online || seen > client.last_seen;
+
+                client.online = online;
+                client.last_seen = client.last_seen.max(seen);
+
+                changed
+            },
+            None => false
+        }
+    }
+
     pub fn get_clients(&self) -> Vec<DatabaseClient> {
         self.data.client.clone()
     }
@@ -155,6 +440,11 @@ impl Database {
         }
     }
 
+    /// True if `name` owns a game server. Such a name cannot be given to anyone else.
+    pub fn owns_syncs(&self, name: &str) -> bool {
+        self.data.synced.iter().any(|s| !s.owner.is_empty() && s.owner == name)
+    }
+
     pub fn get_client_by_ip(&self, ip: &str) -> Option<&DatabaseClient> {
         match self.data.client.iter().find(|c| c.ipv4_address == ip).to_owned() {
             Some(value) => {
@@ -164,12 +454,310 @@ impl Database {
         }
     }
 
+    pub fn get_client_by_public_key(&self, wg_public_key: &str) -> Option<&DatabaseClient> {
+        self.data.client.iter().find(|c| c.wg_public_key == wg_public_key)
+    }
+
+    pub fn new_pending(&mut self, pending: DatabasePending) {
+        self.data.pending.push(pending);
+    }
+
+    pub fn get_pending(&self) -> Vec<DatabasePending> {
+        self.data.pending.clone()
+    }
+
+    pub fn get_pending_by_name(&self, name: &str) -> Option<&DatabasePending> {
+        self.data.pending.iter().find(|p| p.name == name)
+    }
+
+    pub fn get_pending_by_token(&self, token: &str) -> Option<&DatabasePending> {
+        self.data.pending.iter().find(|p| p.token == token)
+    }
+
+    pub fn get_pending_by_public_key(&self, wg_public_key: &str) -> Option<&DatabasePending> {
+        self.data.pending.iter().find(|p| p.wg_public_key == wg_public_key)
+    }
+
+    /// Stores the server info of an approved join request, so the client can pick it up.
+    pub fn approve_pending(&mut self, name: &str, server_info: serde_json::Value) {
+        if let Some(pending) = self.data.pending.iter_mut().find(|p| p.name == name) {
+            pending.server_info = Some(server_info);
+        }
+    }
+
+    /// Drops requests that are older than `timeout` seconds and haven't been approved. Returns their names.
+    pub fn expire_pending(&mut self, timeout: u64) -> Vec<String> {
+        let (expired, kept): (Vec<DatabasePending>, Vec<DatabasePending>) = self.data.pending.drain(..)
+            .partition(|p| p.server_info.is_none() && p.requested + timeout < now());
+
+        self.data.pending = kept;
+
+        expired.into_iter().map(|p| p.name).collect()
+    }
+
+    pub fn remove_pending(&mut self, name: &str) -> Option<DatabasePending> {
+        let position = self.data.pending.iter().position(|p| p.name == name)?;
+
+        Some(self.data.pending.remove(position))
+    }
+
+    pub fn new_invite(&mut self, invite: DatabaseInvite) {
+        self.data.invites.push(invite);
+    }
+
+    /// Returns all invites that can still be used.
+    pub fn get_invites(&self) -> Vec<DatabaseInvite> {
+        self.data.invites.iter()
+            .filter(|i| !i.is_expired())
+            .cloned()
+            .collect()
+    }
+
+    pub fn revoke_invite(&mut self, token: &str) -> Option<DatabaseInvite> {
+        let position = self.data.invites.iter().position(|i| i.token == token)?;
+
+        Some(self.data.invites.remove(position))
+    }
+
+    /// Uses up one use of an invite. Returns false if the invite doesn't exist or expired.
+    pub fn use_invite(&mut self, token: &str) -> bool {
+        self.data.invites.retain(|i| !i.is_expired() && i.uses_left > 0);
+
+        let invite = match self.data.invites.iter_mut().find(|i| i.token == token) {
+            Some(i) => i,
+            None => return false
+        };
+
+        invite.uses_left -= 1;
+        if invite.uses_left == 0 {
+            self.revoke_invite(token);
+        }
+
+        true
+    }
+
+    pub fn new_sync(&mut self, sync: DatabaseSynced) {
+        self.data.synced.push(sync);
+    }
+
+    pub fn remove_sync(&mut self, name: &str) -> Option<DatabaseSynced> {
+        let position = self.data.synced.iter().position(|s| s.name == name)?;
+
+        Some(self.data.synced.remove(position))
+    }
+
+    pub fn get_sync_by_name(&self, name: &str) -> Option<&DatabaseSynced> {
+        self.data.synced.iter().find(|s| s.name == name)
+    }
+
+    /// Returns the lease of a game server if it hasn't expired yet.
+    pub fn get_lease(&self, sync_id: &str) -> Option<&DatabaseLease> {
+        self.get_sync_by_id(sync_id)?
+            .lease.as_ref()
+            .filter(|l| !l.is_expired())
+    }
+
+    pub fn holds_lease(&self, sync_id: &str, ip: &str) -> bool {
+        self.get_lease(sync_id).map(|l| l.holder == ip).unwrap_or(false)
+    }
+
+    /// Grants the lease to `ip` unless someone else holds an active lease. In that case, their lease is returned as error.
+    pub fn acquire_lease(&mut self, sync_id: &str, ip: &str) -> Result<DatabaseLease, DatabaseLease> {
+        if let Some(lease) = self.get_lease(sync_id) {
+            if lease.holder != ip {
+                return Err(lease.clone());
+            }
+        }
+
+        let lease = DatabaseLease {
+            holder: ip.to_string(),
+            acquired: now(),
+            heartbeat: now()
+        };
+
+        let host = self.get_client_by_ip(ip).map(|c| c.name.clone()).unwrap_or_default();
+
+        if let Some(sync) = self.data.synced.iter_mut().find(|s| s.id == sync_id) {
+            sync.lease = Some(lease.clone());
+            sync.last_host = host;
+            sync.last_hosted = now();
+        }
+
+        Ok(lease)
+    }
+
+    /// Extends the lease. Fails if `ip` doesn't hold it (anymore).
+    pub fn renew_lease(&mut self, sync_id: &str, ip: &str) -> Option<DatabaseLease> {
+        if !self.holds_lease(sync_id, ip) {
+            return None;
+        }
+
+        let sync = self.data.synced.iter_mut().find(|s| s.id == sync_id)?;
+        sync.last_hosted = now();
+
+        let lease = sync.lease.as_mut()?;
+        lease.heartbeat = now();
+
+        Some(lease.clone())
+    }
+
+    pub fn release_lease(&mut self, sync_id: &str, ip: &str) -> bool {
+        if !self.holds_lease(sync_id, ip) {
+            return false;
+        }
+
+        match self.data.synced.iter_mut().find(|s| s.id == sync_id) {
+            Some(sync) => {
+                sync.lease = None;
+                sync.last_hosted = now();
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Drops all leases whose holder stopped sending heartbeats. Returns the names of the affected game servers.
+    pub fn expire_leases(&mut self) -> Vec<String> {
+        let mut expired = vec![];
+
+        for sync in self.data.synced.iter_mut() {
+            if let Some(lease) = &sync.lease {
+                if lease.is_expired() {
+                    sync.last_hosted = lease.heartbeat;
+                    sync.lease = None;
+                    expired.push(sync.name.clone());
+                }
+            }
+        }
+
+        expired
+    }
+
+    /// Takes away all leases held by `ip`. Returns the names of the affected game servers.
+    pub fn revoke_leases(&mut self, ip: &str) -> Vec<String> {
+        let mut revoked = vec![];
+
+        for sync in self.data.synced.iter_mut() {
+            if sync.lease.as_ref().map(|l| l.holder == ip).unwrap_or(false) {
+                sync.lease = None;
+                sync.last_hosted = now();
+                revoked.push(sync.name.clone());
+            }
+        }
+
+        revoked
+    }
+
+    pub fn set_server_status(&mut self, sync_id: &str, status: DatabaseServerStatus) {
+        if let Some(sync) = self.data.synced.iter_mut().find(|s| s.id == sync_id) {
+            sync.last_status = Some(status);
+        }
+    }
+
+    /// What `client` may do with a game server. A grant of the client itself wins over the grants of its groups,
+    /// of which the highest wins over the game server's default role. Owners always stay owners.
+    pub fn role_of(&self, sync_id: &str, client: &str) -> Role {
+        let sync = match self.get_sync_by_id(sync_id) {
+            Some(s) => s,
+            None => return Role::None
+        };
+
+        if !sync.owner.is_empty() && sync.owner == client {
+            return Role::Owner;
+        }
+
+        let own = Subject::Client(client.to_string());
+        if let Some(grant) = sync.grants.iter().find(|g| g.subject == own) {
+            return grant.role;
+        }
+
+        let groups: Vec<Subject> = self.data.groups.iter()
+            .filter(|g| g.members.iter().any(|m| m == client))
+            .map(|g| Subject::Group(g.name.clone()))
+            .collect();
+
+        sync.grants.iter()
+            .filter(|g| groups.contains(&g.subject))
+            .map(|g| g.role)
+            .max()
+            .unwrap_or(sync.default_role)
+    }
+
+    /// Replaces the subject's current grant, if any. Returns `None` if there is no such game server.
+    pub fn grant(&mut self, sync_name: &str, subject: Subject, role: Role) -> Option<()> {
+        let sync = self.data.synced.iter_mut().find(|s| s.name == sync_name)?;
+
+        sync.grants.retain(|g| g.subject != subject);
+        sync.grants.push(DatabaseGrant { subject, role });
+
+        Some(())
+    }
+
+    /// Returns false if the subject had no grant.
+    pub fn revoke(&mut self, sync_name: &str, subject: &Subject) -> bool {
+        match self.data.synced.iter_mut().find(|s| s.name == sync_name) {
+            Some(sync) => {
+                let before = sync.grants.len();
+                sync.grants.retain(|g| &g.subject != subject);
+
+                sync.grants.len() != before
+            },
+            None => false
+        }
+    }
+
+    pub fn set_default_role(&mut self, sync_name: &str, role: Role) -> Option<()> {
+        let sync = self.data.synced.iter_mut().find(|s| s.name == sync_name)?;
+        sync.default_role = role;
+
+        Some(())
+    }
+
+    pub fn get_groups(&self) -> Vec<DatabaseGroup> {
+        self.data.groups.clone()
+    }
+
+    /// Creates the group if it doesn't exist yet. Returns false if the client already is a member.
+    pub fn add_to_group(&mut self, group: &str, client: &str) -> bool {
+        let position = match self.data.groups.iter().position(|g| g.name == group) {
+            Some(p) => p,
+            None => {
+                self.data.groups.push(DatabaseGroup { name: group.to_string(), members: vec![] });
+                self.data.groups.len() - 1
+            }
+        };
+
+        let members = &mut self.data.groups[position].members;
+        if members.iter().any(|m| m == client) {
+            return false;
+        }
+
+        members.push(client.to_string());
+        true
+    }
+
+    /// Groups without members are gone, but grants to them stay until revoked. Returns false if the client wasn't a member.
+    pub fn remove_from_group(&mut self, group: &str, client: &str) -> bool {
+        let group = match self.data.groups.iter_mut().find(|g| g.name == group) {
+            Some(g) => g,
+            None => return false
+        };
+
+        let before = group.members.len();
+        group.members.retain(|m| m != client);
+        let removed = group.members.len() != before;
+
+        self.data.groups.retain(|g| !g.members.is_empty());
+
+        removed
+    }
+
     pub fn get_syncs(&self) -> Vec<DatabaseSynced> {
         self.data.clone().synced
     }
 
-    pub fn get_data(&self) -> &DatabaseFormat {
-        &self.data
+    pub fn get_sync_by_id(&self, id: &str) -> Option<&DatabaseSynced> {
+        self.data.synced.iter().find(|s| s.id == id)
     }
 
     pub fn get_wireguard_private_key(&self) -> wireguard_keys::Privkey {
@@ -183,53 +771,215 @@ impl Database {
         }
     }
 
-    /// Writes current state of database to disk. Only if config changed since last write.
+    pub fn get_signing_private_key(&self) -> &str {
+        &self.data.keys.signing_private
+    }
+
+    /// Hands the current state to the storage. Only if it changed since the last flush.
+    /// Returns `None` if it couldn't be stored, so changes only live in memory.
     pub fn flush(&mut self) -> Option<()> {
-        match serde_json::to_string_pretty(&self.data) {
-            Ok(pretty) => {
-                let new_fingerprint = sha256::digest(&pretty);
-                if new_fingerprint == self.fingerprint {
-                    return None;
-                }
+        for _ in 0..FLUSH_ATTEMPTS {
+            // Otherwise we'd overwrite what CLI commands stored in the meantime.
+            self.reload_if_changed();
 
-                self.fingerprint = new_fingerprint;
-
-                match File::create(&self.path) {
-                    Ok(mut file) => {
-                        match file.write_all(pretty.as_bytes()) {
-                            Ok(_) => {
-                                match file.flush() {
-                                    Ok(_) => {
-                                        // We successfully wrote the database to disk.
-                                        Some(())
-                                    },
-                                    Err(error) => {
-                                        error!("Error on flushing buffer to file: {}", error);
-                                        return None;
-                                    }
-                                }
-                                
-                            },
-                            Err(error) => {
-                                error!("Error on writing buffer to file: {}", error);
-                                return None;
-                            }
-                        }
-                    }
+            let new_fingerprint = fingerprint(&self.data);
+            if new_fingerprint == self.fingerprint {
+                return Some(());
+            }
 
-                    Err(error) => {
-                        error!("Database file ({}) couldn't be opened: {}", self.path, error);
-                        None
-                    }
+            match self.storage.store(&self.data) {
+                Ok(_) => {
+                    self.stored = self.data.clone();
+                    self.fingerprint = new_fingerprint;
+                    return Some(());
+                },
+                // Someone stored something between our reload and our store. Merge that as well.
+                Err(StoreError::Changed) => continue,
+                Err(error) => {
+                    error!("{}", error);
+                    return None;
                 }
-            },
-            Err(error) => {
-                error!("Couldn't create JSON string: {}", error);
-                None
-
             }
-        };
+        }
 
+        error!("{} keeps being changed by another process. Try again.", self.storage.location());
         None
     }
 }
+
+/// Prevents flushing the same contents all the time.
+fn fingerprint(data: &DatabaseFormat) -> String {
+    sha256::digest(serde_json::to_string(data).unwrap_or_default())
+}
+
+/// Runs in the background, so CLI commands take effect even while the backend is running.
+///
+/// Also stores what changes all the time (e.g. when a client has been seen last) every few seconds,
+/// instead of writing the database on every request. Changes that matter (e.g. leases) are flushed right away.
+pub async fn sync_storage(database: SharedDatabase) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        let mut database = database.lock().unwrap();
+
+        if database.reload_if_changed() {
+            info!("Picked up changes to the database from {}.", database.storage.location());
+        }
+
+        database.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::storage::json::JsonStorage;
+
+    use super::*;
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcsync-database-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn client(name: &str, key: &str, address: &str) -> DatabaseClient {
+        DatabaseClient {
+            name: name.to_string(),
+            ipv4_address: address.to_string(),
+            last_seen: 0,
+            wg_public_key: key.to_string(),
+            wg_psk: String::from("psk"),
+            online: false
+        }
+    }
+
+    fn sync(id: &str, owner: &str, default_role: Role) -> DatabaseSynced {
+        DatabaseSynced {
+            id: id.to_string(),
+            name: id.to_string(),
+            owner: owner.to_string(),
+            default_role,
+            grants: vec![],
+            start_file: String::from("start"),
+            created: 0,
+            lease: None,
+            last_host: String::new(),
+            last_hosted: 0,
+            last_status: None
+        }
+    }
+
+    #[test]
+    fn removed_clients_stay_gone_after_backend_flush() {
+        let dir = temp_dir();
+        let path = dir.join("database.json").to_string_lossy().to_string();
+        let open = || Database::open(Box::new(JsonStorage::new(path.clone())));
+
+        let mut backend = open();
+        backend.new_client(client("Joe Doe", "key", "10.0.0.2"));
+        backend.flush().unwrap();
+
+        // `mcsync-server remove` runs in its own process.
+        let mut cli = open();
+        cli.remove_client("Joe Doe").unwrap();
+        cli.flush().unwrap();
+
+        // The backend still has Joe in memory and notices him before picking up the removal.
+        backend.set_presence("key", now(), true);
+        backend.flush().unwrap();
+
+        assert!(backend.get_client_by_name("Joe Doe").is_none());
+        assert!(open().get_client_by_name("Joe Doe").is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn removed_owners_leave_nothing_to_inherit() {
+        let dir = temp_dir();
+        let mut db = Database::open(Box::new(JsonStorage::new(dir.join("database.json").to_string_lossy().to_string())));
+
+        db.new_client(client("Joe Doe", "a", "10.0.0.2"));
+        db.new_sync(sync("world", "Joe Doe", Role::None));
+        db.acquire_lease("world", "10.0.0.2").unwrap();
+        db.release_lease("world", "10.0.0.2");
+
+        db.remove_client("Joe Doe").unwrap();
+        assert!(!db.owns_syncs("Joe Doe"));
+        assert_eq!(db.get_sync_by_id("world").unwrap().last_host, "");
+
+        // Someone else joins under the same name later on.
+        db.new_client(client("Joe Doe", "b", "10.0.0.3"));
+        assert_eq!(db.role_of("world", "Joe Doe"), Role::None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn names_of_owners_cannot_be_taken() {
+        let dir = temp_dir();
+        let mut db = Database::open(Box::new(JsonStorage::new(dir.join("database.json").to_string_lossy().to_string())));
+
+        db.new_client(client("Ann", "b", "10.0.0.3"));
+        db.new_sync(sync("world", "Joe Doe", Role::None));
+
+        assert!(db.rename_client("Ann", "Joe Doe").is_none());
+        assert_eq!(db.role_of("world", "Ann"), Role::None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+    fn leased_database(dir: &std::path::Path) -> Database {
+        let mut db = Database::open(Box::new(JsonStorage::new(dir.join("database.json").to_string_lossy().to_string())));
+
+        db.new_client(client("Joe Doe", "a", "10.0.0.2"));
+        db.new_client(client("Ann", "b", "10.0.0.3"));
+        db.new_sync(sync("world", "Joe Doe", Role::Host));
+        db.acquire_lease("world", "10.0.0.2").unwrap();
+
+        db
+    }
+
+    #[test]
+    fn held_leases_cannot_be_taken() {
+        let dir = temp_dir();
+        let mut db = leased_database(&dir);
+
+        assert_eq!(db.acquire_lease("world", "10.0.0.3").unwrap_err().holder, "10.0.0.2");
+        assert!(db.renew_lease("world", "10.0.0.3").is_none());
+        assert!(!db.release_lease("world", "10.0.0.3"));
+        assert!(db.holds_lease("world", "10.0.0.2"));
+
+        // The holder may acquire it again, e.g. after a restart.
+        assert!(db.acquire_lease("world", "10.0.0.2").is_ok());
+        assert!(db.renew_lease("world", "10.0.0.2").is_some());
+
+        assert!(db.release_lease("world", "10.0.0.2"));
+        assert!(db.acquire_lease("world", "10.0.0.3").is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn leases_expire_without_heartbeats() {
+        let dir = temp_dir();
+        let mut db = leased_database(&dir);
+        let heartbeat = now() - LEASE_TIMEOUT - 1;
+
+        db.data.synced[0].lease.as_mut().unwrap().heartbeat = heartbeat + 10;
+        assert!(db.expire_leases().is_empty());
+
+        db.data.synced[0].lease.as_mut().unwrap().heartbeat = heartbeat;
+        assert_eq!(db.expire_leases(), vec![String::from("world")]);
+
+        let sync = db.get_sync_by_id("world").unwrap();
+        assert!(sync.lease.is_none());
+        assert_eq!(sync.last_host, "Joe Doe");
+        assert_eq!(sync.last_hosted, heartbeat);
+
+        assert!(db.renew_lease("world", "10.0.0.2").is_none());
+        assert!(db.acquire_lease("world", "10.0.0.3").is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -15,7 +15,10 @@ use crate::docker::DockerManager;
 pub struct DNSManager {
     docker_instance: DockerManager,
     zone_dir: String,
-    dns_server: String
+    dns_server: String,
+
+    /// Game servers nobody hosts point here, so players reach our fake status server.
+    backend_ip: String
 }
 
 impl DNSManager {
@@ -25,7 +28,8 @@ impl DNSManager {
         Self {
             docker_instance,
             zone_dir,
-            dns_server: String::new()
+            dns_server: String::new(),
+            backend_ip: String::new()
         }
     }
 
@@ -164,7 +168,7 @@ impl DNSManager {
     }
 
     // Once called, domains "backend.mc" will be set.
-    // Those names are reservered and cannot be created by users.
+    // Those names are reservered and cannot be created by users. Call `restart_dns` afterwards to apply them.
     pub async fn setup_service_domains(&mut self) {
         match self.docker_instance.get_dns_container().await {
             Some(dns) => {
@@ -190,13 +194,42 @@ impl DNSManager {
                     .unwrap();
     
                     self.set_or_update_record("backend", &own_ip, true).await;
+                    self.backend_ip = own_ip;
                 },
             None => {
                 error!("Cannot find own container. Did you rename your containers? The name has to contain \"backend\" and \"mcsync\" somewhere e.g. \"mcsync-backend-1\".");
-                return;
             }
         }
+    }
+
+    /// Writes the records of all game servers at once. Call `restart_dns` afterwards to apply them.
+    pub async fn setup_game_server_domains(&self, game_servers: Vec<(String, Option<String>)>) {
+        for (name, host) in game_servers {
+            let ip = host.unwrap_or_else(|| self.backend_ip.clone());
+
+            if !ip.is_empty() {
+                self.set_or_update_record(&name, &ip, false).await;
+            }
+        }
+    }
 
+    /// Points `name.mc` to the client hosting it or back to the backend if nobody does (`host` is None).
+    pub async fn point_game_server(&self, name: &str, host: Option<&str>) -> Option<()> {
+        let ip = host.unwrap_or(&self.backend_ip);
+
+        if ip.is_empty() {
+            warn!("Cannot point {}.mc to the backend since its IP is unknown.", name);
+            return None;
+        }
+
+        // Restarting the resolver interrupts everyone else, so only do it if something changed.
+        if self.query(name).await.as_deref() == Some(ip) {
+            return Some(());
+        }
+
+        self.set_or_update_record(name, ip, false).await?;
         self.restart_dns().await;
+
+        Some(())
     }
 }
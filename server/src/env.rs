@@ -4,6 +4,8 @@ use ipnet::Ipv4Net;
 use paris::{info, error};
 use reqwest::Url;
 
+use crate::storage::Backend;
+
 /*
  Collection of functions to retrive & parse env input.
 */
@@ -66,6 +68,31 @@ pub fn get_wg_config() -> String {
     }
 }
 
+pub fn get_wg_socket() -> String {
+    match std::env::var("WG_SOCKET") {
+        Ok(s) => s,
+        Err(_) => {
+            String::from("/var/run/wireguard/wg0.sock")
+        }
+    }
+}
+
+/// Port of the public enrollment listener that `mcsync join` talks to.
+pub fn get_enrollment_port() -> u16 {
+    match std::env::var("ENROLLMENT_PORT") {
+        Ok(port) => {
+            match port.parse() {
+                Ok(p) => p,
+                Err(error) => {
+                    error!("ENROLLMENT_PORT {} is not a valid port: {}", port, error);
+                    exit(1);
+                }
+            }
+        },
+        Err(_) => 8081
+    }
+}
+
 pub fn get_docker_path() -> String {
     match std::env::var("DOCKER_SOCKET") {
         Ok(d) => format!("unix://{}", d),
@@ -91,13 +118,33 @@ pub fn get_dns_zone_dir() -> String {
     }
 }
 
+/// Where the database is stored, either "json" (default) or "sqlite".
+pub fn get_database_backend() -> Backend {
+    match std::env::var("DATABASE_BACKEND") {
+        Ok(backend) => {
+            match backend.to_lowercase().as_str() {
+                "json" => Backend::Json,
+                "sqlite" => Backend::Sqlite,
+                _ => {
+                    error!("DATABASE_BACKEND {} is unknown. Use \"json\" or \"sqlite\".", backend);
+                    exit(1);
+                }
+            }
+        },
+        Err(_) => Backend::Json
+    }
+}
+
 pub fn get_database_path() -> String {
     match std::env::var("DATABASE_PATH") {
         Ok(file_path) => {
             file_path
         },
         Err(_) => {
-            String::from("/database.json")
+            match get_database_backend() {
+                Backend::Json => String::from("/database.json"),
+                Backend::Sqlite => String::from("/database.sqlite")
+            }
         }
     }
 }
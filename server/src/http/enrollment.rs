@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use actix_web::{get, post, web::{Data, Json, Path}, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use mcsync_proto::{enrollment::{JoinRequest, JoinResponse}, time::now};
+use paris::{error, info, success, warn};
+
+use crate::{
+    database::{Database, DatabaseClient, DatabasePending},
+    routines::{accept::allocate_address, invite::generate_token, server_info},
+    signing::{self, Identity},
+    wireguard::Wireguard
+};
+
+use super::state::AppState;
+
+/// Nobody has that many friends asking at once. Protects the database from being flooded.
+const MAX_PENDING: usize = 16;
+
+/// Open requests from a single address. Keeps one sender from using up MAX_PENDING on their own.
+const MAX_PENDING_PER_ADDRESS: usize = 2;
+
+/// Requests nobody approved within a day are dropped, so junk doesn't block enrollment until an admin denies it.
+const PENDING_TIMEOUT: u64 = 24 * 60 * 60;
+
+const MAX_NAME_LENGTH: usize = 32;
+
+/// Runs the enrollment listener. Unlike everything else it's reachable from outside the tunnel,
+/// so it must not expose anything but join requests.
+pub async fn listen(state: Arc<AppState>, port: u16) {
+    let state = Data::from(state);
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .service(join)
+            .service(poll)
+            .app_data(state.clone())
+    })
+    .workers(1)
+    .bind(("0.0.0.0", port));
+
+    let server = match server {
+        Ok(s) => s.run(),
+        Err(error) => {
+            error!("Cannot listen on port {} for join requests: {}", port, error);
+            return;
+        }
+    };
+
+    info!("Accept join requests on port {}", port);
+    warn!("Join requests use plain HTTP. Invites and preshared keys can be read on the way unless there is a TLS proxy in front.");
+
+    if let Err(error) = server.await {
+        error!("Enrollment listener stopped: {}", error);
+    }
+}
+
+/// Queues a join request until an admin approves it using `mcsync-server approve`.
+/// Clients with a valid invite are accepted right away and receive their server info instead.
+#[post("/join")]
+pub async fn join(req: HttpRequest, body: Json<JoinRequest>, state: Data<AppState>) -> impl Responder {
+    let address = req.connection_info().peer_addr().unwrap_or_default().to_string();
+    let name = body.name.trim().to_string();
+
+    if !body.client_info.is_supported() {
+        return HttpResponse::BadRequest().body("Your client info has been created using a newer format. Update the server first.");
+    }
+
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH || name.chars().any(|c| c.is_control()) {
+        return HttpResponse::BadRequest().body(format!("Invalid name. Use up to {} printable characters.", MAX_NAME_LENGTH));
+    }
+
+    if wireguard_keys::Pubkey::from_base64(&body.client_info.wireguard_pub).is_err() {
+        return HttpResponse::BadRequest().body("Invalid WireGuard public key.");
+    }
+
+    let fingerprint = match signing::verify_client_info(&body.client_info) {
+        Ok(f) => f.unwrap_or_default(),
+        Err(error) => return HttpResponse::BadRequest().body(error)
+    };
+
+    if let Some(invite) = &body.invite {
+        return join_with_invite(&name, &body.client_info.wireguard_pub, invite, &state).await;
+    }
+
+    let mut db = state.database.lock().unwrap();
+
+    // The admin approves using the CLI, which writes to the file directly.
+    db.reload_if_changed();
+
+    for expired in db.expire_pending(PENDING_TIMEOUT) {
+        info!("Nobody approved the join request of {} in time. Dropped it.", expired);
+    }
+
+    if let Some(response) = check_conflicts(&db, &name, &body.client_info.wireguard_pub) {
+        return response;
+    }
+
+    let pending = db.get_pending();
+    if pending.len() >= MAX_PENDING {
+        return HttpResponse::TooManyRequests().body("There are too many open join requests. Try again later.");
+    }
+
+    if pending.iter().filter(|p| p.address == address).count() >= MAX_PENDING_PER_ADDRESS {
+        warn!("Refused join request of {} from {}: Too many open requests from there", name, address);
+        return HttpResponse::TooManyRequests().body("You already have open join requests. Wait until an admin looked at them.");
+    }
+
+    let token = generate_token(32);
+
+    db.new_pending(DatabasePending {
+        name: name.clone(),
+        wg_public_key: body.client_info.wireguard_pub.clone(),
+        token: token.clone(),
+        requested: now(),
+        address: address.clone(),
+        fingerprint,
+        server_info: None
+    });
+    db.flush();
+
+    info!("{} ({}) wants to join. Approve using \"mcsync-server approve {}\"", name, address, name);
+
+    HttpResponse::Accepted().json(JoinResponse { token })
+}
+
+/// Responds with 202 while the request is pending and with the server info once it has been approved.
+/// The server info is handed out only once.
+#[get("/join/{token}")]
+pub async fn poll(token: Path<String>, state: Data<AppState>) -> impl Responder {
+    let mut db = state.database.lock().unwrap();
+    db.reload_if_changed();
+
+    let pending = match db.get_pending_by_token(&token) {
+        Some(p) => p.clone(),
+        None => {
+            return HttpResponse::NotFound().body("Unknown join request. It may have been denied or expired.");
+        }
+    };
+
+    match pending.server_info {
+        Some(server_info) => {
+            db.remove_pending(&pending.name);
+            db.flush();
+
+            info!("{} picked up their server info", pending.name);
+
+            HttpResponse::Ok().json(server_info)
+        },
+        None => HttpResponse::Accepted().body("Waiting for approval.")
+    }
+}
+
+/// Admits the client right away. The lock is only held while the database changes, not while talking to Docker.
+async fn join_with_invite(name: &str, wireguard_pub: &str, invite: &str, state: &AppState) -> HttpResponse {
+    let (wireguard, docker) = (&state.wireguard, &state.docker);
+
+    let (client, public_key, identity) = {
+        let mut db = state.database.lock().unwrap();
+        db.reload_if_changed();
+
+        if let Some(response) = check_conflicts(&db, name, wireguard_pub) {
+            return response;
+        }
+
+        let address = match allocate_address(&db) {
+            Some(a) => a,
+            None => {
+                error!("{} wants to join but there is no more space in the user subnet.", name);
+                return HttpResponse::ServiceUnavailable().body("The server is full. Ask an admin to make some room.");
+            }
+        };
+
+        if !db.use_invite(invite) {
+            warn!("{} tried to join using an invalid invite", name);
+            return HttpResponse::Forbidden().body("This invite is invalid, used up or expired.");
+        }
+
+        let client = DatabaseClient {
+            name: name.to_string(),
+            ipv4_address: address,
+            last_seen: 0,
+            wg_public_key: wireguard_pub.to_string(),
+            wg_psk: Wireguard::generate_psk(),
+            online: false
+        };
+
+        db.new_client(client.clone());
+        db.flush();
+        wireguard.regenerate_config(&db);
+
+        (client, db.get_wireguard_private_key().pubkey().to_base64(), Identity::from_database(&db))
+    };
+
+    // Only restart WireGuard (and disconnect everyone) if the peer cannot be added at runtime.
+    if wireguard.apply_peer(&client.wg_public_key, &client.wg_psk, &client.ipv4_address).is_none() {
+        warn!("Couldn't add peer at runtime. Restart WireGuard instead ...");
+        wireguard.restart(docker).await;
+    }
+
+    match server_info::collect(public_key, &identity, docker, &client).await {
+        Some(server_info) => {
+            success!("{} joined using an invite and got {}", name, client.ipv4_address);
+            HttpResponse::Ok().json(server_info)
+        },
+        None => HttpResponse::InternalServerError().body(format!(
+            "You have been accepted but your server info couldn't be created. Ask an admin to run \"mcsync-server server-info {}\".", name
+        ))
+    }
+}
+
+/// Names and keys have to be unique among clients and open requests. Names of owners are taken as well.
+fn check_conflicts(db: &Database, name: &str, wireguard_pub: &str) -> Option<HttpResponse> {
+    if db.get_client_by_name(name).is_some() || db.get_pending_by_name(name).is_some() || db.owns_syncs(name) {
+        return Some(HttpResponse::Conflict().body(format!("The name \"{}\" is already taken. Choose another one.", name)));
+    }
+
+    if db.get_client_by_public_key(wireguard_pub).is_some() || db.get_pending_by_public_key(wireguard_pub).is_some() {
+        return Some(HttpResponse::Conflict().body("You already are a member or asked to join before."));
+    }
+
+    None
+}
@@ -1,3 +1,50 @@
-fn ep_status() -> &'static str {
-    "ok"
-}
\ No newline at end of file
+use actix_web::{get, web::{Data, ReqData}, HttpResponse, Responder};
+use mcsync_proto::status::{StatusGameServer, StatusMember, StatusResponse};
+
+use crate::{build, database::{Database, Role}};
+
+use super::{middleware::Member, state::AppState};
+
+/// Collects what `GET /status` responds with. The CLI prints the same.
+pub fn status(db: &Database) -> StatusResponse {
+    let servers = db.get_syncs().into_iter()
+        .map(|s| {
+            let host = db.get_lease(&s.id)
+                .map(|l| db.get_client_by_ip(&l.holder).map(|c| c.name.clone()).unwrap_or_else(|| l.holder.clone()));
+
+            StatusGameServer {
+                name: s.name,
+                version: s.last_status.as_ref().map(|st| st.version.clone()),
+                online_players: if host.is_some() { s.last_status.as_ref().map(|st| st.online_players).unwrap_or(0) } else { 0 },
+                max_players: s.last_status.as_ref().map(|st| st.max_players).unwrap_or(0),
+                host,
+                last_host: s.last_host,
+                last_hosted: s.last_hosted
+            }
+        })
+        .collect();
+
+    let members = db.get_clients().into_iter()
+        .map(|c| StatusMember { name: c.name, online: c.online, last_seen: c.last_seen })
+        .collect();
+
+    StatusResponse {
+        version: build::PKG_VERSION.to_string(),
+        servers,
+        members
+    }
+}
+
+/// Everything `mcsync status` shows: Which game servers are hosted and who is around.
+/// Game servers the member may not play on are left out.
+#[get("/status")]
+pub async fn get_status(member: ReqData<Member>, state: Data<AppState>) -> impl Responder {
+    let db = state.database.lock().unwrap();
+
+    let mut response = status(&db);
+    response.servers.retain(|s| {
+        db.get_sync_by_name(&s.name).map(|sync| db.role_of(&sync.id, &member.name) >= Role::Player).unwrap_or(false)
+    });
+
+    HttpResponse::Ok().json(response)
+}
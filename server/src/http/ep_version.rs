@@ -0,0 +1,18 @@
+use actix_web::{get, HttpResponse, Responder};
+use mcsync_proto::version::{features, VersionResponse, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+use crate::build;
+
+/// Everything this backend offers. The enrollment listener runs alongside the backend, so it counts as well.
+const FEATURES: [&str; 4] = [features::SYNC, features::LEASES, features::STATUS, features::ENROLLMENT];
+
+/// Lets clients find out whether they can talk to us before they try. Mixed versions fail early with a clear message.
+#[get("/version")]
+pub async fn get_version() -> impl Responder {
+    HttpResponse::Ok().json(VersionResponse {
+        version: build::PKG_VERSION.to_string(),
+        protocol_min: MIN_PROTOCOL_VERSION,
+        protocol_max: PROTOCOL_VERSION,
+        features: FEATURES.iter().map(|f| f.to_string()).collect()
+    })
+}
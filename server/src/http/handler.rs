@@ -1,6 +1,6 @@
 use std::path::Path;
 use std::process::exit;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use actix_web::web::{Data, resource};
 use dav_server::{DavHandler, DavConfig};
@@ -8,26 +8,20 @@ use dav_server::actix::{DavRequest, DavResponse};
 use dav_server::fakels::FakeLs;
 use dav_server::localfs::LocalFs;
 use paris::{ error};
-use serde::{Serialize, Deserialize};
 
-use actix_web::{HttpServer, get, App, Responder, HttpRequest};
-use crate::database::{Database, DatabaseSynced};
+use actix_web::{HttpServer, App};
 use crate::env::get_minecraft_save_path;
 
-use super::cache::Cache;
-use super::middleware::ClientSeenFactory;
+use super::middleware::ClientAuthFactory;
+use super::state::AppState;
+use super::{server, lease, ep_status, ep_version};
 
 pub struct HttpHandler {
-    database: Database,
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct ReturnSync {
-    sync: Vec<DatabaseSynced>
+    state: Arc<AppState>
 }
 
 impl HttpHandler {
-    pub async fn new(database: Database) -> Self {
+    pub async fn new(state: Arc<AppState>) -> Self {
         let saves_path = get_minecraft_save_path();
         let saves_dir = Path::new(&saves_path);
         if !saves_dir.exists() {
@@ -40,11 +34,11 @@ impl HttpHandler {
             exit(1);
         }
 
-        Self { database }
+        Self { state }
     }
 
     pub async fn listen(&self) {
-        start(self.database.clone()).await;
+        start(self.state.clone()).await;
     }
 }
 
@@ -57,50 +51,36 @@ pub async fn dav_handler(req: DavRequest, davhandler: Data<DavHandler>) -> DavRe
     }
 }
 
-async fn start(db: Database) {
-    let _ = HttpServer::new(move || {
-        let db_clone = db.clone();
-        let data = Arc::new(Mutex::new(Cache::new()));
+async fn start(state: Arc<AppState>) {
+    // All workers share the same state. Otherwise every worker would flush its own diverging copy.
+    let state = Data::from(state);
 
+    let _ = HttpServer::new(move || {
         let dav_server = DavHandler::builder()
             .filesystem(LocalFs::new("/tmp", false, false, false))
             .locksystem(FakeLs::new())
             .build_handler();
 
         App::new()
-            //.service(get_root)
-            //.service(get_status)
-            //.service(set_status)
             .service(resource("/dav/{tail:.*}").to(dav_handler))
-            .app_data(Data::new(db_clone.clone()))
+            .service(server::list_servers)
+            .service(server::create_server)
+            .service(server::get_manifest)
+            .service(server::download)
+            .service(server::delta)
+            .service(server::transfer)
+            .service(lease::acquire)
+            .service(lease::heartbeat)
+            .service(lease::release)
+            .service(ep_status::get_status)
+            .service(ep_version::get_version)
+            .app_data(state.clone())
             .app_data(Data::new(dav_server.clone()))
-            .wrap(ClientSeenFactory::new(db_clone))
+            .wrap(ClientAuthFactory::new(state.clone()))
     })
+    // Docker decides who can reach this port. `ClientAuthFactory` makes sure only members get any further.
     .bind(("0.0.0.0", 8080))
     .unwrap()
     .run()
     .await;
 }
-
-#[get("/")]
-async fn get_root(req: HttpRequest, db: Data<Database>) -> impl Responder {
-    String::from("mcsync server v0.1")
-}
-
-#[get("/<sync>")]
-async fn get_status(req: HttpRequest, cache: Data<Cache>, sync: String) -> impl Responder {
-    format!("{}", cache.can_sync(req.connection_info().peer_addr().unwrap().to_string(), sync))
-}
-
-#[get("/<sync>/allow")]
-async fn set_status(req: HttpRequest, cache: Data<Cache>, sync: String) -> impl Responder {
-    // cache.borrow_mut().add_sync(req.connection_info().peer_addr().unwrap().to_string(), sync);
-
-    String::new()
-}
-
-
-/*#[get("/sync")]
-fn sync_list(db: State<Database>) -> Json<ReturnSync> {
-    Json(ReturnSync { sync: db.get_syncs()} )
-}*/
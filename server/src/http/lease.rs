@@ -0,0 +1,117 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{post, delete, web::{Data, Path, ReqData}, HttpResponse, Responder, http::StatusCode};
+use mcsync_proto::sync::LeaseResponse;
+use paris::{info, warn};
+
+use crate::database::{Database, DatabaseLease, Role, LEASE_TIMEOUT};
+
+use super::{middleware::Member, server::check_role, state::AppState};
+
+fn lease_response(db: &Database, lease: &DatabaseLease) -> LeaseResponse {
+    LeaseResponse {
+        holder: db.get_client_by_ip(&lease.holder)
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| lease.holder.clone()),
+        acquired: lease.acquired,
+        expires: lease.heartbeat + LEASE_TIMEOUT
+    }
+}
+
+/// Acquires the exclusive right to host a game server. Requires the role host.
+/// Responds with 423 if someone else is hosting.
+#[post("/server/{id}/lease")]
+pub async fn acquire(member: ReqData<Member>, id: Path<String>, state: Data<AppState>) -> impl Responder {
+    let ip = &member.address;
+
+    let (name, lease) = {
+        let mut db = state.database.lock().unwrap();
+
+        if let Some(response) = check_role(&member, &id, &db, Role::Host) {
+            return response;
+        }
+
+        let name = db.get_sync_by_id(&id).map(|s| s.name.clone()).unwrap_or_default();
+
+        match db.acquire_lease(&id, ip) {
+            Ok(lease) => {
+                db.flush();
+                info!("{} is now hosting {}", member.name, name);
+
+                (name, lease_response(&db, &lease))
+            },
+            Err(current) => {
+                return HttpResponse::build(StatusCode::LOCKED).json(lease_response(&db, &current));
+            }
+        }
+    };
+
+    // Friends join <name>.mc no matter who is hosting.
+    state.dns.point_game_server(&name, Some(ip)).await;
+
+    HttpResponse::Ok().json(lease)
+}
+
+/// Hosts have to call this regularly (well within LEASE_TIMEOUT) to keep their lease.
+/// Once they lost the role host, the lease runs out.
+#[post("/server/{id}/lease/heartbeat")]
+pub async fn heartbeat(member: ReqData<Member>, id: Path<String>, state: Data<AppState>) -> impl Responder {
+    let mut db = state.database.lock().unwrap();
+
+    if let Some(response) = check_role(&member, &id, &db, Role::Host) {
+        return response;
+    }
+
+    match db.renew_lease(&id, &member.address) {
+        Some(lease) => {
+            db.flush();
+            HttpResponse::Ok().json(lease_response(&db, &lease))
+        },
+        None => HttpResponse::Conflict().body("You don't hold the lease of this game server (anymore).")
+    }
+}
+
+#[delete("/server/{id}/lease")]
+pub async fn release(member: ReqData<Member>, id: Path<String>, state: Data<AppState>) -> impl Responder {
+    let name = {
+        let mut db = state.database.lock().unwrap();
+
+        if !db.release_lease(&id, &member.address) {
+            return HttpResponse::Conflict().body("You don't hold the lease of this game server.");
+        }
+
+        db.flush();
+
+        let name = db.get_sync_by_id(&id).map(|s| s.name.clone()).unwrap_or_default();
+        info!("{} stopped hosting {}", member.name, name);
+
+        name
+    };
+
+    state.dns.point_game_server(&name, None).await;
+
+    HttpResponse::Ok().finish()
+}
+
+/// Runs in the background and points game servers back to the backend once their host vanished without releasing.
+pub async fn expire_leases(state: Arc<AppState>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(LEASE_TIMEOUT / 6)).await;
+
+        let expired = {
+            let mut db = state.database.lock().unwrap();
+            let expired = db.expire_leases();
+
+            if !expired.is_empty() {
+                db.flush();
+            }
+
+            expired
+        };
+
+        for name in expired {
+            warn!("Host of {} stopped sending heartbeats. Lease expired.", name);
+            state.dns.point_game_server(&name, None).await;
+        }
+    }
+}
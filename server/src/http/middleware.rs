@@ -1,87 +1,122 @@
-use std::{future::{ready, Ready}, rc::Rc, cell::RefCell};
+use std::{future::{ready, Ready}, net::Ipv4Addr};
 
 use actix_web::{
+    body::EitherBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error,
+    web::Data,
+    Error, HttpMessage, HttpResponse,
 };
 use futures_util::future::LocalBoxFuture;
-use paris::info;
+use ipnet::Ipv4Net;
+use paris::{info, warn};
 
-use crate::database::Database;
+use crate::env;
 
-pub struct ClientSeenFactory {
-    db: Rc<RefCell<Database>>
+use super::state::AppState;
+
+/// The client a request came from. Every handler behind `ClientAuthFactory` can take it using `ReqData<Member>`.
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name: String,
+
+    /// Address of the client within the user subnet.
+    pub address: String
 }
 
-impl ClientSeenFactory {
-    pub fn new(db: Database) -> Self {
-        ClientSeenFactory { db: Rc::new(RefCell::new(db)) }
+/// Only lets members through. Anything else (requests from outside the tunnel, removed clients and mcsync's
+/// own services in the tool subnet) is answered with 403 before it reaches a handler.
+pub struct ClientAuthFactory {
+    state: Data<AppState>,
+    user_subnet: Ipv4Net
+}
+
+impl ClientAuthFactory {
+    pub fn new(state: Data<AppState>) -> Self {
+        ClientAuthFactory { state, user_subnet: env::get_user_subnet() }
     }
 }
 
-impl<S, B> Transform<S, ServiceRequest> for ClientSeenFactory
+impl<S, B> Transform<S, ServiceRequest> for ClientAuthFactory
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
     B: 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type InitError = ();
-    type Transform = ClientSeenMiddleware<S>;
+    type Transform = ClientAuthMiddleware<S>;
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(ClientSeenMiddleware { service, db: self.db.clone() }))
+        ready(Ok(ClientAuthMiddleware { service, state: self.state.clone(), user_subnet: self.user_subnet }))
     }
 }
 
-pub struct ClientSeenMiddleware<S> {
+pub struct ClientAuthMiddleware<S> {
     service: S,
-    db: Rc<RefCell<Database>>
+    state: Data<AppState>,
+    user_subnet: Ipv4Net
 }
 
-impl<S, B> Service<ServiceRequest> for ClientSeenMiddleware<S>
+impl<S> ClientAuthMiddleware<S> {
+    /// Looks up who sent the request. Also remembers that we have seen them.
+    fn authenticate(&self, ip: &str) -> Option<Member> {
+        let address: Ipv4Addr = ip.parse().ok()?;
+        if !self.user_subnet.contains(&address) {
+            return None;
+        }
+
+        let mut db = self.state.database.lock().unwrap();
+
+        // Removed clients are out right away. Only reads the database if another process changed it.
+        db.reload_if_changed();
+
+        let member = db.get_client_by_ip(ip).map(|c| Member { name: c.name.clone(), address: c.ipv4_address.clone() })?;
+
+        // Stored by `database::sync_storage` within a few seconds. Writing the database per request is too much.
+        db.seen_client(ip);
+
+        Some(member)
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for ClientAuthMiddleware<S>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
     B: 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let binding = req.connection_info().clone();
-        let ip = binding.peer_addr().unwrap();
-
-        let mut db_mut = self.db.borrow_mut();
-        db_mut.seen_client(ip);
-        db_mut.flush();
-
-        let client = db_mut.get_client_by_ip(ip);
-
-        info!(
-            "{} ({}) -- {} {}",
-            ip,
-            if client.is_some() {
-                client.unwrap().name.clone()
-            } else {
-                String::from("")
-            },
-            req.method(),
-            req.uri().path()
-        );
+        // The peer address is the one of the TCP connection, so forwarded headers can't fake it.
+        let ip = req.peer_addr().map(|a| a.ip().to_string()).unwrap_or_default();
+
+        let member = match self.authenticate(&ip) {
+            Some(m) => m,
+            None => {
+                warn!("Refused {} {} from {}: Not a member", req.method(), req.uri().path(), ip);
+
+                let response = HttpResponse::Forbidden().body("Only members can talk to the backend. Connect through the tunnel.");
+                return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+            }
+        };
+
+        info!("{} ({}) -- {} {}", ip, member.name, req.method(), req.uri().path());
+
+        req.extensions_mut().insert(member);
 
         let fut = self.service.call(req);
 
         Box::pin(async move {
             let res = fut.await?;
 
-            Ok(res)
+            Ok(res.map_into_left_body())
         })
     }
 }
-
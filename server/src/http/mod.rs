@@ -1,4 +1,8 @@
 pub mod handler;
+pub mod state;
 pub mod server;
 pub mod middleware;
-pub mod cache;
+pub mod lease;
+pub mod ep_status;
+pub mod ep_version;
+pub mod enrollment;
@@ -1,25 +1,286 @@
-use serde::{Serialize, Deserialize};
+use std::{collections::HashSet, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
 
-// Pasted from client
+use actix_web::{get, post, web::{self, Data, Json, Path, Payload, ReqData}, HttpResponse, Responder, http::StatusCode};
+use lazy_static::lazy_static;
+use paris::{error, warn, success};
+use regex::Regex;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
 
-#[derive(Serialize, Deserialize)]
-pub struct CreateServer {
-    server_name: String
+use mcsync_proto::sync::{CreateServer, CreateServerResponse, DeltaClient, ServerEntry, TransferResponse};
+
+use crate::{database::{Database, DatabaseSynced, Role}, manifest::{self, is_safe_path}, staging::{Staging, PendingSync, Progress, TransferError}};
+
+use super::{middleware::Member, state::AppState};
+
+lazy_static! {
+    /// Game server names become domains (e.g. survival1.mc), so they have to be valid DNS labels.
+    static ref SERVER_NAME: Regex = Regex::new(r"^[a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?$").unwrap();
+}
+
+/// Those names are used by mcsync's own services.
+const RESERVED_NAMES: [&str; 2] = ["backend", "dns"];
+
+/// Lists all game servers the member may play on together with their current generation.
+#[get("/server")]
+pub async fn list_servers(member: ReqData<Member>, state: Data<AppState>) -> impl Responder {
+    let syncs: Vec<DatabaseSynced> = {
+        let db = state.database.lock().unwrap();
+
+        db.get_syncs().into_iter()
+            .filter(|s| db.role_of(&s.id, &member.name) >= Role::Player)
+            .collect()
+    };
+
+    let servers: Vec<ServerEntry> = syncs.into_iter()
+        .map(|s| ServerEntry {
+            generation: manifest::load(&s.id).map(|m| m.generation).unwrap_or(0),
+            id: s.id,
+            name: s.name,
+            owner: s.owner,
+            start_file: s.start_file
+        })
+        .collect();
+
+    HttpResponse::Ok().json(servers)
+}
+
+/// Registers a new game server. The requesting client becomes its owner.
+#[post("/server")]
+pub async fn create_server(member: ReqData<Member>, body: Json<CreateServer>, state: Data<AppState>) -> impl Responder {
+    let id = {
+        let mut db = state.database.lock().unwrap();
+
+        if !SERVER_NAME.is_match(&body.server_name) || RESERVED_NAMES.contains(&body.server_name.as_str()) {
+            return HttpResponse::BadRequest().body(
+                "Invalid name. Only lowercase letters, digits and dashes are allowed, since it will be used as domain."
+            );
+        }
+
+        if db.get_sync_by_name(&body.server_name).is_some() {
+            return HttpResponse::Conflict().body(format!("There is already a game server called {}.", body.server_name));
+        }
+
+        if !body.start_file.is_empty() && !is_safe_path(&body.start_file) {
+            return HttpResponse::BadRequest().body(format!("Invalid start file: {}", body.start_file));
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let save_dir = manifest::sync_dir(&id);
+
+        if let Err(error) = std::fs::create_dir_all(&save_dir) {
+            error!("Couldn't create save directory {}: {}", save_dir.display(), error);
+            return HttpResponse::InternalServerError().body("Couldn't create save directory.");
+        }
+
+        db.new_sync(DatabaseSynced {
+            id: id.clone(),
+            name: body.server_name.clone(),
+            owner: member.name.clone(),
+
+            // Everyone may host it, until the owner asks an admin to restrict it.
+            default_role: Role::Host,
+            grants: vec![],
+            start_file: body.start_file.clone(),
+            created: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            lease: None,
+            last_host: String::new(),
+            last_hosted: 0,
+            last_status: None
+        });
+        db.flush();
+
+        success!("{} created game server {} ({})", member.name, body.server_name, id);
+
+        id
+    };
+
+    // Nobody hosts it yet, so players should reach our status responder.
+    state.dns.point_game_server(&body.server_name, None).await;
+
+    HttpResponse::Created().json(CreateServerResponse { server_uuid: id })
+}
+
+/// Compares the client's files with the manifest of the latest generation.
+/// Responds with 409 if the client didn't pull the latest generation first.
+#[post("/server/{id}/delta")]
+pub async fn delta(member: ReqData<Member>, id: Path<String>, body: Json<DeltaClient>, state: Data<AppState>) -> impl Responder {
+    if let Some(response) = check_host(&member, &id, &state.database) {
+        return response;
+    }
+
+    if let Some(file) = body.files.iter().find(|f| !is_safe_path(&f.path)) {
+        warn!("Refuse delta for {} because of invalid path {}", id, file.path);
+        return HttpResponse::BadRequest().body(format!("Invalid path: {}", file.path));
+    }
+
+    let mut paths = HashSet::new();
+    if let Some(file) = body.files.iter().find(|f| !paths.insert(f.path.as_str())) {
+        warn!("Refuse delta for {} because {} is listed more than once", id, file.path);
+        return HttpResponse::BadRequest().body(format!("Duplicate path: {}", file.path));
+    }
+
+    let manifest = match manifest::load(&id) {
+        Some(m) => m,
+        None => {
+            return HttpResponse::InternalServerError().body("Manifest of this game server is corrupt.");
+        }
+    };
+
+    if manifest::is_outdated(&manifest, body.last_sync) {
+        return HttpResponse::Conflict().body(format!(
+            "Server has a newer generation ({}) than your local copy ({}).",
+            manifest.generation, body.last_sync
+        ));
+    }
+
+    let mut delta = manifest::delta(&manifest, &body.files);
+
+    if delta.new.is_empty() && delta.modified.is_empty() && delta.removed.is_empty() {
+        delta.generation = Some(manifest.generation);
+        return HttpResponse::Ok().json(delta);
+    }
+
+    let mut expected = delta.new.clone();
+    expected.extend(delta.modified.clone());
+
+    let staging = Staging::new(&id);
+    let pending = PendingSync {
+        base_generation: manifest.generation,
+        files: body.files.clone(),
+        expected
+    };
+
+    if staging.begin(&pending).is_none() {
+        return HttpResponse::InternalServerError().body("Couldn't prepare upload.");
+    }
+
+    // Only deleted files, so there is no transfer that could trigger the commit.
+    if pending.expected.is_empty() {
+        match finish(&id).await {
+            Ok(Progress::Committed(generation)) => delta.generation = Some(generation),
+            Ok(Progress::Remaining(_)) => (),
+            Err(error) => return transfer_error(&id, error)
+        }
+    }
+
+    HttpResponse::Ok().json(delta)
+}
+
+/// Returns the manifest of the current generation. Clients use it to pull the newest files.
+#[get("/server/{id}/manifest")]
+pub async fn get_manifest(member: ReqData<Member>, id: Path<String>, state: Data<AppState>) -> impl Responder {
+    if let Some(response) = check_role(&member, &id, &state.database.lock().unwrap(), Role::Player) {
+        return response;
+    }
+
+    match manifest::load(&id) {
+        Some(m) => HttpResponse::Ok().json(m),
+        None => HttpResponse::InternalServerError().body("Manifest of this game server is corrupt.")
+    }
+}
+
+/// Streams a single file of the current generation.
+#[get("/server/{id}/file/{path:.*}")]
+pub async fn download(member: ReqData<Member>, path: Path<(String, String)>, state: Data<AppState>) -> impl Responder {
+    let (id, file_path) = path.into_inner();
+
+    if let Some(response) = check_role(&member, &id, &state.database.lock().unwrap(), Role::Player) {
+        return response;
+    }
+
+    if !is_safe_path(&file_path) {
+        return HttpResponse::BadRequest().body(format!("Invalid path: {}", file_path));
+    }
+
+    let full_path = manifest::current_dir(&id).join("files").join(&file_path);
+
+    match tokio::fs::File::open(&full_path).await {
+        Ok(file) => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .streaming(ReaderStream::new(file)),
+        Err(_) => HttpResponse::NotFound().body(format!("{} is not part of the current generation.", file_path))
+    }
+}
+
+/// Receives one file of a previously negotiated delta. The last file triggers the commit of a new generation.
+#[post("/server/{id}/transfer/{file_id}")]
+pub async fn transfer(member: ReqData<Member>, path: Path<(String, u32)>, payload: Payload, state: Data<AppState>) -> impl Responder {
+    let (id, file_id) = path.into_inner();
+
+    if let Some(response) = check_host(&member, &id, &state.database) {
+        return response;
+    }
+
+    let staging = Staging::new(&id);
+
+    if let Err(error) = staging.receive(file_id, payload).await {
+        return transfer_error(&id, error);
+    }
+
+    match finish(&id).await {
+        Ok(Progress::Remaining(remaining)) => HttpResponse::Ok().json(TransferResponse { remaining, generation: None }),
+        Ok(Progress::Committed(generation)) => HttpResponse::Ok().json(TransferResponse { remaining: 0, generation: Some(generation) }),
+        Err(TransferError::UnknownFile) => HttpResponse::Conflict().body("Upload has been superseded by another one."),
+        Err(error) => transfer_error(&id, error)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct CreateServerResponse {
-    server_uuid: String
+/// Commits the upload of a game server once all files arrived. Runs on a blocking thread, since it waits for other commits.
+async fn finish(id: &str) -> Result<Progress, TransferError> {
+    let staging = Staging::new(id);
+
+    match web::block(move || staging.finish()).await {
+        Ok(result) => result,
+        Err(error) => Err(TransferError::Io(error.to_string()))
+    }
 }
 
-// End pasted from client
+/// Returns the error response unless the game server exists and the member has at least `role`.
+pub fn check_role(member: &Member, id: &str, db: &Database, role: Role) -> Option<HttpResponse> {
+    if db.get_sync_by_id(id).is_none() {
+        return Some(HttpResponse::NotFound().body(format!("Game server {} doesn't exist.", id)));
+    }
 
-// #[post("/server", data = "<create_server>")]
-// pub fn create_server(create_server: Json<CreateServer>) -> String {
-//     create_server.server_name.clone()
-// }
-//
-// #[post("/server/<id>/transfer", data = "<data>")]
-// pub fn transfer(id: String, data: Data) -> Result<String, std::io::Error> {
-//     Ok(String::from("progress"))
-// }
+    let current = db.role_of(id, &member.name);
+    if current < role {
+        return Some(HttpResponse::Forbidden().body(format!(
+            "This requires the role \"{}\" on this game server, yours is \"{}\". Ask its owner.", role, current
+        )));
+    }
+
+    None
+}
+
+/// Only the current host of a game server may push changes. Returns the error response otherwise.
+fn check_host(member: &Member, id: &str, db: &Mutex<Database>) -> Option<HttpResponse> {
+    let db = db.lock().unwrap();
+
+    if let Some(response) = check_role(member, id, &db, Role::Host) {
+        return Some(response);
+    }
+
+    if !db.holds_lease(id, &member.address) {
+        return Some(HttpResponse::build(StatusCode::LOCKED).body("You need to hold the lease of this game server in order to push changes."));
+    }
+
+    None
+}
+
+fn transfer_error(id: &str, error: TransferError) -> HttpResponse {
+    match error {
+        TransferError::UnknownFile => {
+            HttpResponse::NotFound().body("This file is not part of the current upload. Negotiate a delta first.")
+        },
+        TransferError::Mismatch => {
+            HttpResponse::UnprocessableEntity().body("File doesn't match the negotiated size or hash.")
+        },
+        TransferError::Outdated => {
+            HttpResponse::Conflict().body("Someone else synced this game server in the meantime.")
+        },
+        TransferError::Io(message) => {
+            error!("Sync of {} failed: {}", id, message);
+            HttpResponse::InternalServerError().body("Couldn't store file.")
+        }
+    }
+}
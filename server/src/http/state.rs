@@ -0,0 +1,14 @@
+use std::sync::Arc;
+
+use crate::{database::SharedDatabase, dns::DNSManager, docker::DockerManager, wireguard::Wireguard};
+
+/// Everything the backend's HTTP workers share. Created once in `main` and handed to every worker of both
+/// listeners, so a change made by one worker is visible to all others right away.
+///
+/// Leases are part of the database, so they are shared (and stored) along with it.
+pub struct AppState {
+    pub database: SharedDatabase,
+    pub dns: Arc<DNSManager>,
+    pub wireguard: Arc<Wireguard>,
+    pub docker: DockerManager
+}
@@ -1,30 +1,57 @@
-#![feature(proc_macro_hygiene, decl_macro)]
-#![feature(iter_advance_by)]
-
 mod database;
 mod dns;
 mod docker;
 mod http;
+mod manifest;
+mod minecraft;
+mod presence;
+mod staging;
 mod wireguard;
 mod routines;
+mod signing;
 mod env;
+mod storage;
+mod utils;
 
-use std::io::{Read, Write};
-use std::net::{Shutdown, TcpListener, TcpStream};
 use std::process::exit;
-use std::thread;
+use std::sync::{Arc, Mutex};
 
-use database::Database;
+use database::{Database, Role};
 use dns::DNSManager;
 use docker::DockerManager;
+use http::state::AppState;
+use minecraft::StatusServer;
+use presence::Presence;
 
+use clap::{Parser, Subcommand};
 use lazy_static::lazy_static;
 use routines::accept::Accept;
+use routines::add_to_group::AddToGroup;
+use routines::approve::Approve;
+use routines::deny::Deny;
+use routines::delete_sync::DeleteSync;
+use routines::grant::Grant;
+use routines::import_database::ImportDatabase;
+use routines::invite::Invite;
+use routines::list_invites::ListInvites;
+use routines::list_clients::ListClients;
+use routines::list_groups::ListGroups;
+use routines::list_pending::ListPending;
+use routines::list_syncs::ListSyncs;
 use routines::remove::RemoveUser;
+use routines::remove_from_group::RemoveFromGroup;
+use routines::rename_client::RenameClient;
+use routines::revoke::Revoke;
+use routines::revoke_invite::RevokeInvite;
+use routines::server_info::ShowServerInfo;
+use routines::show_client::ShowClient;
+use routines::show_permissions::ShowPermissions;
+use routines::status::Status;
 
 use paris::{error, info};
 use shadow_rs::{shadow, Format};
 
+use crate::signing::Identity;
 use crate::wireguard::Wireguard;
 
 lazy_static! {
@@ -33,34 +60,271 @@ lazy_static! {
 
 shadow!(build);
 
+#[derive(Parser, Debug)]
+#[clap(author = "Nicolas Klier aka Mondei1", version, about = "Backend of mcsync. Runs the server if no command is given.", long_about = None)]
+struct App {
+    #[clap(default_value_t = false, global = true, long)]
+    /// Print JSON instead of text. Applies to list-clients, show-client, list-syncs, list-pending, list-invites, list-groups, permissions and status.
+    json: bool,
+
+    #[clap(subcommand)]
+    command: Option<Action>
+}
+
+#[derive(Debug, Subcommand)]
+enum Action {
+    /// Accept a new client. Reads its client info (.mcsc) from stdin and prints the server info (.mcss).
+    Accept {
+        /// Name of the new client. A random one is picked if omitted.
+        name: Option<String>,
+
+        #[clap(long)]
+        /// Refuse the client info unless it has this fingerprint. Ask your friend for it.
+        fingerprint: Option<String>
+    },
+
+    /// Print the fingerprint of this server. Friends compare it when importing their server info.
+    Fingerprint,
+
+    /// List everyone who asked to join using "mcsync join".
+    ListPending,
+
+    /// Accept a join request. The client receives its server info automatically.
+    Approve {
+        /// Name the client asked for.
+        name: String
+    },
+
+    /// Reject a join request.
+    Deny {
+        /// Name the client asked for.
+        name: String
+    },
+
+    /// Create an invite. Whoever uses it with "mcsync join" gets in without approval.
+    Invite {
+        #[clap(default_value_t = 1, long)]
+        /// How many people may use it.
+        uses: u32,
+
+        #[clap(default_value = "24h", long, value_parser = utils::time::parse_duration)]
+        /// How long it stays valid, e.g. 30m, 24h or 7d.
+        expires: u64
+    },
+
+    /// List all invites that can still be used.
+    ListInvites,
+
+    /// Make an invite unusable.
+    RevokeInvite {
+        token: String
+    },
+
+    /// Print the server info (.mcss) of an existing client again.
+    ServerInfo {
+        /// Name of the client.
+        name: String,
+
+        #[clap(default_value_t = false, long)]
+        /// Replace the client's preshared key. Their old server info stops working.
+        rotate_psk: bool
+    },
+
+    /// Remove a client and revoke its access immediately.
+    Remove {
+        /// Name of the client.
+        name: String
+    },
+
+    /// List all clients.
+    ListClients,
+
+    /// Give a client another name.
+    RenameClient {
+        /// Current name of the client.
+        name: String,
+        new_name: String
+    },
+
+    /// Print everything known about a client.
+    ShowClient {
+        /// Name of the client.
+        name: String
+    },
+
+    /// List all game servers.
+    ListSyncs,
+
+    /// Delete a game server including all of its files on the backend.
+    DeleteSync {
+        /// Name of the game server.
+        name: String,
+
+        #[clap(default_value_t = false, long)]
+        /// Delete it even though someone is hosting it right now.
+        force: bool
+    },
+
+    /// Give a client or group a role on a game server. Without --client and --group, this sets the role of everyone else.
+    Grant {
+        /// Name of the game server.
+        name: String,
+
+        #[clap(value_enum)]
+        /// "player" may pull and play, "host" may also host and push changes, "none" doesn't see the game server at all.
+        role: Role,
+
+        #[clap(long, conflicts_with = "group")]
+        /// Name of the client.
+        client: Option<String>,
+
+        #[clap(long)]
+        /// Name of the group.
+        group: Option<String>
+    },
+
+    /// Take back the role of a client or group. The default role of the game server applies again.
+    Revoke {
+        /// Name of the game server.
+        name: String,
+
+        #[clap(long, conflicts_with = "group")]
+        /// Name of the client.
+        client: Option<String>,
+
+        #[clap(long)]
+        /// Name of the group.
+        group: Option<String>
+    },
+
+    /// Print who may do what with a game server.
+    Permissions {
+        /// Name of the game server.
+        name: String
+    },
+
+    /// Add a client to a group. The group is created if it doesn't exist yet.
+    AddToGroup {
+        group: String,
+
+        /// Name of the client.
+        client: String
+    },
+
+    /// Remove a client from a group. Groups without members are gone.
+    RemoveFromGroup {
+        group: String,
+
+        /// Name of the client.
+        client: String
+    },
+
+    /// List all groups and their members.
+    ListGroups,
+
+    /// Copy an existing database.json into SQLite. Requires DATABASE_BACKEND=sqlite.
+    ImportDatabase {
+        /// Location of the database.json.
+        path: String,
+
+        #[clap(default_value_t = false, long)]
+        /// Replace the current database even though it isn't empty.
+        force: bool
+    },
+
+    /// Print version, game servers and clients.
+    Status
+}
+
 #[tokio::main]
 async fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let args: App = App::parse();
 
     let mut database = Database::new();
     let docker_manager = DockerManager::new().await;
 
     let endpoint: String = env::get_endpoint();
 
-    let mut vpn = Wireguard::new(database.clone(), endpoint);
-    vpn.regenerate_config();
+    let mut vpn = Wireguard::new(&database, endpoint);
+    vpn.regenerate_config(&database);
 
-    let subroutine = args.get(1);
-
-    if let Some(..) = subroutine {
-        match subroutine.unwrap().to_lowercase().as_str() {
-            "accept" => {
-                Accept::new(&mut database, &mut vpn, &docker_manager).execute().await;
-            }
-            "remove" => {
-                RemoveUser::new(&mut database).execute();
-            }
-            _ => {
-                error!("Unknown argument");
+    if let Some(command) = args.command {
+        match command {
+            Action::Accept { name, fingerprint } => {
+                Accept::new(&mut database, &mut vpn, &docker_manager).execute(name, fingerprint).await;
+            },
+            Action::Fingerprint => {
+                println!("{}", Identity::from_database(&database).fingerprint());
+            },
+            Action::ListPending => {
+                ListPending::new(&database).execute(args.json);
+            },
+            Action::Approve { name } => {
+                Approve::new(&mut database, &mut vpn, &docker_manager).execute(&name).await;
+            },
+            Action::Deny { name } => {
+                Deny::new(&mut database).execute(&name);
+            },
+            Action::Invite { uses, expires } => {
+                Invite::new(&mut database).execute(uses, expires);
+            },
+            Action::ListInvites => {
+                ListInvites::new(&database).execute(args.json);
+            },
+            Action::RevokeInvite { token } => {
+                RevokeInvite::new(&mut database).execute(&token);
+            },
+            Action::ServerInfo { name, rotate_psk } => {
+                ShowServerInfo::new(&mut database, &mut vpn, &docker_manager).execute(&name, rotate_psk).await;
+            },
+            Action::Remove { name } => {
+                RemoveUser::new(&mut database, &mut vpn, &docker_manager).execute(&name).await;
+            },
+            Action::ListClients => {
+                ListClients::new(&database).execute(args.json);
+            },
+            Action::RenameClient { name, new_name } => {
+                RenameClient::new(&mut database).execute(&name, &new_name);
+            },
+            Action::ShowClient { name } => {
+                ShowClient::new(&database).execute(&name, args.json);
+            },
+            Action::ListSyncs => {
+                ListSyncs::new(&database).execute(args.json);
+            },
+            Action::DeleteSync { name, force } => {
+                DeleteSync::new(&mut database, &docker_manager).execute(&name, force).await;
+            },
+            Action::Grant { name, role, client, group } => {
+                Grant::new(&mut database).execute(&name, role, client, group);
+            },
+            Action::Revoke { name, client, group } => {
+                Revoke::new(&mut database).execute(&name, client, group);
+            },
+            Action::Permissions { name } => {
+                ShowPermissions::new(&database).execute(&name, args.json);
+            },
+            Action::AddToGroup { group, client } => {
+                AddToGroup::new(&mut database).execute(&group, &client);
+            },
+            Action::RemoveFromGroup { group, client } => {
+                RemoveFromGroup::new(&mut database).execute(&group, &client);
+            },
+            Action::ListGroups => {
+                ListGroups::new(&database).execute(args.json);
+            },
+            Action::ImportDatabase { path, force } => {
+                ImportDatabase::new(&mut database).execute(&path, force);
+            },
+            Action::Status => {
+                Status::new(&database).execute(args.json);
             }
         }
 
-        database.flush();
+        if database.flush().is_none() {
+            error!("Couldn't save the database. Your changes are lost, see previous errors.");
+            exit(1);
+        }
 
         exit(0);
     }
@@ -86,61 +350,35 @@ async fn main() {
     let mut dns_manager = DNSManager::new(docker_manager.clone());
     dns_manager.setup_service_domains().await;
 
-    let http_server = http::handler::HttpHandler::new(database).await;
-    http_server.listen().await;
+    // Game servers point to their host or to us, if nobody is hosting.
+    let game_servers = database.get_syncs().into_iter()
+        .map(|s| (s.name.clone(), database.get_lease(&s.id).map(|l| l.holder.clone())))
+        .collect();
+    dns_manager.setup_game_server_domains(game_servers).await;
+    dns_manager.restart_dns().await;
 
-    // fake_status_server();
-}
+    let state = Arc::new(AppState {
+        database: Arc::new(Mutex::new(database)),
+        dns: Arc::new(dns_manager),
+        wireguard: Arc::new(vpn),
+        docker: docker_manager
+    });
 
-fn fake_status_server() {
-    thread::spawn(move || {
-        let listener = TcpListener::bind("0.0.0.0:25565").unwrap();
-        // accept connections and process them, spawning a new thread for each one
-        println!("Server listening on port 3333");
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    println!("New connection: {}", stream.peer_addr().unwrap());
-                    thread::spawn(move || {
-                        // connection succeeded
-                        handle_client(stream)
-                    });
-                }
-                Err(e) => {
-                    println!("Error: {}", e);
-                    /* connection failed */
-                }
-            }
-        }
-        // close the socket server
-        drop(listener);
-    })
-    .join()
-    .unwrap();
-}
+    tokio::spawn(database::sync_storage(state.database.clone()));
+    tokio::spawn(StatusServer::new(state.database.clone()).listen());
+    tokio::spawn(StatusServer::new(state.database.clone()).poll_hosts());
+    tokio::spawn(Presence::new(state.wireguard.clone(), state.database.clone()).track());
+    tokio::spawn(http::lease::expire_leases(state.clone()));
 
-fn handle_client(mut stream: TcpStream) {
-    let mut data = [0 as u8; 50]; // using 50 byte buffer
-    let mut count = 0;
-    while match stream.read(&mut data) {
-        Ok(size) => {
-            // echo everything!
-            println!("Received: {:?}", &data[0..size]);
-            stream.write(&data[0..size]).unwrap();
-            true
-        }
-        Err(_) => {
-            println!(
-                "An error occurred, terminating connection with {}",
-                stream.peer_addr().unwrap()
-            );
-            stream.shutdown(Shutdown::Both).unwrap();
-            false
-        }
-    } {
-        if count > 5 {
-            break;
-        }
-        count += 1;
+    let enrollment = http::enrollment::listen(state.clone(), env::get_enrollment_port());
+    let http_server = http::handler::HttpHandler::new(state.clone()).await;
+
+    // Actix servers cannot be spawned onto another thread, so both run right here. Both stop on SIGINT and SIGTERM.
+    tokio::join!(http_server.listen(), enrollment);
+
+    // Changes that are only stored every few seconds would be lost otherwise.
+    if state.database.lock().unwrap().flush().is_none() {
+        error!("Couldn't save the database before shutting down. See previous errors.");
+        exit(1);
     }
 }
@@ -0,0 +1,189 @@
+use std::{fs::File, io::Write, path::{Path, PathBuf, Component}, collections::{HashMap, HashSet}};
+
+use mcsync_proto::sync::{FileHash, DeltaServer, Manifest};
+use paris::error;
+
+use crate::env;
+
+/// Root directory of a game server inside MINECRAFT_SAVES.
+pub fn sync_dir(sync_id: &str) -> PathBuf {
+    Path::new(&env::get_minecraft_save_path()).join(sync_id)
+}
+
+/// Directory of the live generation. This is a symlink that gets swapped once a new generation is complete.
+pub fn current_dir(sync_id: &str) -> PathBuf {
+    sync_dir(sync_id).join("current")
+}
+
+/// Loads the manifest of the current generation. A game server that never has been synced has an empty manifest.
+pub fn load(sync_id: &str) -> Option<Manifest> {
+    let path = current_dir(sync_id).join("manifest.json");
+
+    if !path.exists() {
+        return Some(Manifest::default());
+    }
+
+    match File::open(&path) {
+        Ok(file) => {
+            match serde_json::from_reader(file) {
+                Ok(manifest) => Some(manifest),
+                Err(error) => {
+                    error!("Manifest at {} is corrupt: {}", path.display(), error);
+                    None
+                }
+            }
+        },
+        Err(error) => {
+            error!("Couldn't open manifest at {}: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+/// Writes the manifest into a generation directory and makes sure it actually hit the disk.
+pub fn store(manifest: &Manifest, generation_dir: &Path) -> Option<()> {
+    let path = generation_dir.join("manifest.json");
+
+    let json = match serde_json::to_string_pretty(manifest) {
+        Ok(j) => j,
+        Err(error) => {
+            error!("Couldn't create JSON string: {}", error);
+            return None;
+        }
+    };
+
+    match File::create(&path) {
+        Ok(mut file) => {
+            match file.write_all(json.as_bytes()).and_then(|_| file.sync_all()) {
+                Ok(_) => Some(()),
+                Err(error) => {
+                    error!("Error on writing manifest {}: {}", path.display(), error);
+                    None
+                }
+            }
+        },
+        Err(error) => {
+            error!("Manifest file ({}) couldn't be created: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+/// Compares the files of a client with a manifest. Files are matched by their path.
+///
+/// New and modified files keep the ID the client assigned to them, so the client can refer to them during transfer.
+pub fn delta(manifest: &Manifest, client_files: &[FileHash]) -> DeltaServer {
+    let known: HashMap<&str, &FileHash> = manifest.files.iter()
+        .map(|f| (f.path.as_str(), f))
+        .collect();
+
+    let mut new = vec![];
+    let mut modified = vec![];
+
+    for file in client_files {
+        match known.get(file.path.as_str()) {
+            Some(existing) => {
+                if existing.hash != file.hash || existing.size != file.size {
+                    modified.push(file.clone());
+                }
+            },
+            None => new.push(file.clone())
+        }
+    }
+
+    let reported: HashSet<&str> = client_files.iter().map(|f| f.path.as_str()).collect();
+
+    let removed = manifest.files.iter()
+        .filter(|f| !reported.contains(f.path.as_str()))
+        .cloned()
+        .collect();
+
+    DeltaServer { new, modified, removed, generation: None }
+}
+
+/// A client has to pull the latest generation before it may push changes on top of it.
+pub fn is_outdated(manifest: &Manifest, last_sync: u64) -> bool {
+    last_sync < manifest.generation
+}
+
+/// Paths sent by clients must stay inside the game server's directory. Each file has exactly one valid path,
+/// so empty segments (e.g. "world//level.dat") are refused as well.
+pub fn is_safe_path(path: &str) -> bool {
+    if path.split('/').any(|segment| segment.is_empty()) {
+        return false;
+    }
+
+    Path::new(path).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(id: u32, path: &str, hash: &str) -> FileHash {
+        FileHash { id, size: hash.len() as u64, path: path.to_string(), hash: hash.to_string() }
+    }
+
+    fn paths(files: &[FileHash]) -> Vec<&str> {
+        files.iter().map(|f| f.path.as_str()).collect()
+    }
+
+    #[test]
+    fn delta_finds_new_modified_and_removed_files() {
+        let manifest = Manifest {
+            generation: 5,
+            files: vec![file(0, "level.dat", "a"), file(1, "server.properties", "b"), file(2, "old.txt", "c")]
+        };
+        let client = vec![file(7, "level.dat", "a"), file(8, "server.properties", "bb"), file(9, "new.txt", "d")];
+
+        let delta = delta(&manifest, &client);
+
+        assert_eq!(paths(&delta.new), vec!["new.txt"]);
+        assert_eq!(paths(&delta.modified), vec!["server.properties"]);
+        assert_eq!(paths(&delta.removed), vec!["old.txt"]);
+
+        // The client refers to files using its own IDs during transfer.
+        assert_eq!(delta.new[0].id, 9);
+        assert_eq!(delta.modified[0].id, 8);
+    }
+
+    #[test]
+    fn delta_of_unchanged_files_is_empty() {
+        let files = vec![file(0, "level.dat", "a"), file(1, "world/region/r.0.0.mca", "b")];
+        let delta = delta(&Manifest { generation: 5, files: files.clone() }, &files);
+
+        assert!(delta.new.is_empty() && delta.modified.is_empty() && delta.removed.is_empty());
+    }
+
+    #[test]
+    fn safe_paths_stay_inside() {
+        assert!(is_safe_path("level.dat"));
+        assert!(is_safe_path("world/region/r.0.0.mca"));
+
+        assert!(!is_safe_path(""));
+        assert!(!is_safe_path(".."));
+        assert!(!is_safe_path("../database.json"));
+        assert!(!is_safe_path("world/../../database.json"));
+        assert!(!is_safe_path("/etc/passwd"));
+        assert!(!is_safe_path("./level.dat"));
+    }
+
+    #[test]
+    fn empty_segments_are_refused() {
+        // Otherwise the same file could be listed twice under different names.
+        assert!(!is_safe_path("world//level.dat"));
+        assert!(!is_safe_path("world/"));
+        assert!(!is_safe_path("//etc/passwd"));
+    }
+
+    #[test]
+    fn pushing_requires_the_latest_generation() {
+        let manifest = Manifest { generation: 5, files: vec![] };
+
+        assert!(is_outdated(&manifest, 4));
+        assert!(!is_outdated(&manifest, 5));
+
+        // A game server that has never been synced accepts anything.
+        assert!(!is_outdated(&Manifest::default(), 0));
+    }
+}
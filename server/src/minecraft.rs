@@ -0,0 +1,493 @@
+use std::time::Duration;
+
+use mcsync_proto::time::format_ago;
+use paris::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt}, net::{TcpListener, TcpStream}, time::timeout};
+
+use crate::database::{SharedDatabase, DatabaseSynced, DatabaseServerStatus, Role};
+
+/// Port Minecraft clients connect to by default.
+pub const MINECRAFT_PORT: u16 = 25565;
+
+/// How often hosted Minecraft servers get asked for their version and player count.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Packets of the handshake & status state are tiny. Anything larger is nonsense.
+const MAX_PACKET_LENGTH: usize = 32 * 1024;
+
+/// Answers the Server List Ping of Minecraft clients whenever nobody hosts a game server.
+/// That way players see who hosted it last instead of "Can't connect to server".
+/// Only members who may play on a game server see anything about it.
+///
+/// See https://wiki.vg/Server_List_Ping
+pub struct StatusServer {
+    database: SharedDatabase
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct StatusVersion {
+    name: String,
+    protocol: i32
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct StatusPlayers {
+    max: u32,
+    online: u32
+}
+
+/// Part of the JSON a Minecraft server answers to a status request. We only care about those fields.
+#[derive(Deserialize, Serialize, Debug)]
+struct StatusResponse {
+    version: StatusVersion,
+    players: StatusPlayers
+}
+
+/// First packet of every modern client.
+#[derive(Debug, PartialEq, Eq)]
+struct Handshake {
+    protocol: i32,
+
+    /// Address the player entered, e.g. "survival1.mc".
+    address: String,
+
+    /// 1 for status, 2 for login.
+    next_state: i32
+}
+
+impl StatusServer {
+    pub fn new(database: SharedDatabase) -> Self {
+        Self { database }
+    }
+
+    pub async fn listen(self) {
+        let listener = match TcpListener::bind(("0.0.0.0", MINECRAFT_PORT)).await {
+            Ok(l) => l,
+            Err(error) => {
+                error!("Cannot listen on port {} for Minecraft status requests: {}", MINECRAFT_PORT, error);
+                return;
+            }
+        };
+
+        info!("Answer Minecraft status requests on port {}", MINECRAFT_PORT);
+
+        loop {
+            let (stream, address) = match listener.accept().await {
+                Ok(s) => s,
+                Err(error) => {
+                    warn!("Failed to accept Minecraft connection: {}", error);
+                    continue;
+                }
+            };
+
+            let database = self.database.clone();
+
+            tokio::spawn(async move {
+                let peer = address.ip().to_string();
+
+                if timeout(Duration::from_secs(10), handle_connection(stream, database, peer)).await.is_err() {
+                    warn!("Minecraft connection from {} timed out", address);
+                }
+            });
+        }
+    }
+
+    /// Asks every hosted Minecraft server for its version and player count, so we can tell once it's offline.
+    /// Stored by `database::sync_storage` along with the other soft changes.
+    pub async fn poll_hosts(self) {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let hosted: Vec<(String, String)> = {
+                let db = self.database.lock().unwrap();
+
+                db.get_syncs().into_iter()
+                    .filter_map(|s| db.get_lease(&s.id).map(|l| (s.id.clone(), l.holder.clone())))
+                    .collect()
+            };
+
+            for (sync_id, holder) in hosted {
+                if let Some(status) = ping(&holder, MINECRAFT_PORT).await {
+                    let mut db = self.database.lock().unwrap();
+
+                    db.set_server_status(&sync_id, DatabaseServerStatus {
+                        version: status.version.name,
+                        protocol: status.version.protocol,
+                        max_players: status.players.max,
+                        online_players: status.players.online
+                    });
+                }
+            }
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, database: SharedDatabase, peer: String) -> Option<()> {
+    let mut first = [0u8; 1];
+    stream.peek(&mut first).await.ok()?;
+
+    // Clients older than 1.7 send 0xFE instead of a handshake.
+    if first[0] == 0xFE {
+        return handle_legacy_ping(stream).await;
+    }
+
+    let Handshake { protocol, address, next_state } = parse_handshake(&read_packet(&mut stream).await?)?;
+    let sync = find_sync(&database, &address, &peer);
+
+    match next_state {
+        // Status
+        1 => {
+            let status_request = read_packet(&mut stream).await?;
+            if status_request.first() != Some(&0x00) {
+                return None;
+            }
+
+            let (version, version_protocol, max_players) = match sync.as_ref().and_then(|s| s.last_status.as_ref()) {
+                Some(status) => (status.version.clone(), status.protocol, status.max_players),
+                None => (String::from("mcsync"), protocol, 20)
+            };
+
+            let response = json!({
+                "version": { "name": version, "protocol": version_protocol },
+                "players": { "max": max_players, "online": 0, "sample": [] },
+                "description": { "text": motd(&database, sync.as_ref(), &address) }
+            });
+
+            let mut payload = vec![];
+            write_varint(&mut payload, 0x00);
+            write_string(&mut payload, &response.to_string());
+            write_packet(&mut stream, &payload).await?;
+
+            // Client measures latency afterwards. We simply echo its payload.
+            if let Some(ping) = read_packet(&mut stream).await {
+                if ping.first() == Some(&0x01) {
+                    write_packet(&mut stream, &ping).await?;
+                }
+            }
+        },
+        // Login: Nobody is there to let the player in, tell them why.
+        2 => {
+            let mut payload = vec![];
+            write_varint(&mut payload, 0x00);
+            write_string(&mut payload, &json!({ "text": motd(&database, sync.as_ref(), &address) }).to_string());
+            write_packet(&mut stream, &payload).await?;
+        },
+        _ => {}
+    }
+
+    Some(())
+}
+
+/// Answers the ping of Minecraft 1.4 - 1.6 (and understood by older versions as well).
+async fn handle_legacy_ping<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S) -> Option<()> {
+    // Those clients don't tell us which server they want to reach in a reliable way, so they don't learn about any.
+    let mut buffer = [0u8; 256];
+    let _ = stream.read(&mut buffer).await.ok()?;
+
+    stream.write_all(&legacy_response("Nobody is hosting right now.")).await.ok()
+}
+
+fn legacy_response(motd: &str) -> Vec<u8> {
+    let text = format!("§1\0{}\0{}\0{}\0{}\0{}", 127, "mcsync", motd, 0, 20);
+    let encoded: Vec<u16> = text.encode_utf16().collect();
+
+    let mut response = vec![0xFF];
+    response.extend_from_slice(&(encoded.len() as u16).to_be_bytes());
+    for c in encoded {
+        response.extend_from_slice(&c.to_be_bytes());
+    }
+
+    response
+}
+
+fn parse_handshake(packet: &[u8]) -> Option<Handshake> {
+    let mut cursor = packet;
+
+    if read_varint(&mut cursor)? != 0x00 {
+        return None;
+    }
+
+    let protocol = read_varint(&mut cursor)?;
+    let address = read_string(&mut cursor)?;
+
+    // Skip port
+    cursor = cursor.get(2..)?;
+    let next_state = read_varint(&mut cursor)?;
+
+    Some(Handshake { protocol, address, next_state })
+}
+
+/// Players connect to e.g. "survival1.mc", so the first label is the game server's name.
+///
+/// The port is reachable from outside the tunnel, so only members who may play on a game server learn about it.
+/// Everyone else is told there is no such game server.
+fn find_sync(database: &SharedDatabase, address: &str, peer: &str) -> Option<DatabaseSynced> {
+    // Forge appends "\0FML\0" to the address.
+    let host = address.split('\0').next().unwrap_or_default();
+    let name = host.split('.').next().unwrap_or_default().to_lowercase();
+
+    let db = database.lock().unwrap();
+    let client = db.get_client_by_ip(peer)?;
+    let sync = db.get_sync_by_name(&name)?;
+
+    if db.role_of(&sync.id, &client.name) < Role::Player {
+        return None;
+    }
+
+    Some(sync.clone())
+}
+
+fn motd(database: &SharedDatabase, sync: Option<&DatabaseSynced>, address: &str) -> String {
+    let sync = match sync {
+        Some(s) => s,
+        None => {
+            return if address.is_empty() {
+                String::from("Nobody is hosting right now.")
+            } else {
+                format!("There is no game server at {}.", address.split('\0').next().unwrap_or_default())
+            };
+        }
+    };
+
+    let db = database.lock().unwrap();
+
+    if let Some(lease) = db.get_lease(&sync.id) {
+        let host = db.get_client_by_ip(&lease.holder).map(|c| c.name.clone()).unwrap_or_else(|| lease.holder.clone());
+        return format!("{} is hosted by {} right now. Refresh in a moment.", sync.name, host);
+    }
+
+    if sync.last_host.is_empty() {
+        return format!("{} is offline — nobody hosted it yet.", sync.name);
+    }
+
+    format!("{} is offline — last hosted by {} {}.", sync.name, sync.last_host, format_ago(sync.last_hosted))
+}
+
+/// Asks a Minecraft server for its status, the same way the server list does.
+async fn ping(host: &str, port: u16) -> Option<StatusResponse> {
+    let request = async {
+        let mut stream = TcpStream::connect((host, port)).await.ok()?;
+
+        let mut handshake = vec![];
+        write_varint(&mut handshake, 0x00);
+        write_varint(&mut handshake, -1);
+        write_string(&mut handshake, host);
+        handshake.extend_from_slice(&port.to_be_bytes());
+        write_varint(&mut handshake, 1);
+
+        write_packet(&mut stream, &handshake).await?;
+        write_packet(&mut stream, &[0x00]).await?;
+
+        let response = read_packet(&mut stream).await?;
+        let mut cursor = response.as_slice();
+
+        if read_varint(&mut cursor)? != 0x00 {
+            return None;
+        }
+
+        serde_json::from_str::<StatusResponse>(&read_string(&mut cursor)?).ok()
+    };
+
+    timeout(Duration::from_secs(5), request).await.ok().flatten()
+}
+
+async fn read_packet<R: AsyncRead + Unpin>(stream: &mut R) -> Option<Vec<u8>> {
+    let mut header = vec![];
+
+    // The length is a VarInt of at most 5 bytes. `read_varint` refuses it if it doesn't end by then.
+    while header.len() < 5 {
+        let byte = stream.read_u8().await.ok()?;
+        header.push(byte);
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    let length = read_varint(&mut header.as_slice())?;
+    if length <= 0 || length as usize > MAX_PACKET_LENGTH {
+        return None;
+    }
+
+    let mut buffer = vec![0u8; length as usize];
+    stream.read_exact(&mut buffer).await.ok()?;
+
+    Some(buffer)
+}
+
+async fn write_packet<W: AsyncWrite + Unpin>(stream: &mut W, payload: &[u8]) -> Option<()> {
+    let mut packet = vec![];
+    write_varint(&mut packet, payload.len() as i32);
+    packet.extend_from_slice(payload);
+
+    stream.write_all(&packet).await.ok()
+}
+
+fn read_varint(cursor: &mut &[u8]) -> Option<i32> {
+    let mut value: i32 = 0;
+
+    for position in 0..5 {
+        let (byte, rest) = cursor.split_first()?;
+        *cursor = rest;
+
+        value |= ((byte & 0x7F) as i32) << (7 * position);
+
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+fn write_varint(buffer: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+
+    loop {
+        if value & !0x7F == 0 {
+            buffer.push(value as u8);
+            return;
+        }
+
+        buffer.push((value & 0x7F | 0x80) as u8);
+        value >>= 7;
+    }
+}
+
+fn read_string(cursor: &mut &[u8]) -> Option<String> {
+    let length = read_varint(cursor)?;
+    if length < 0 || length as usize > cursor.len() {
+        return None;
+    }
+
+    let (string, rest) = cursor.split_at(length as usize);
+    *cursor = rest;
+
+    String::from_utf8(string.to_vec()).ok()
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    write_varint(buffer, value.len() as i32);
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(value: i32) -> Vec<u8> {
+        let mut buffer = vec![];
+        write_varint(&mut buffer, value);
+
+        buffer
+    }
+
+    fn handshake(address: &str, next_state: i32) -> Vec<u8> {
+        let mut packet = varint(0x00);
+        write_varint(&mut packet, 760);
+        write_string(&mut packet, address);
+        packet.extend_from_slice(&MINECRAFT_PORT.to_be_bytes());
+        write_varint(&mut packet, next_state);
+
+        packet
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 255, 25565, 2097151, i32::MAX, -1, i32::MIN] {
+            assert_eq!(read_varint(&mut varint(value).as_slice()), Some(value), "{}", value);
+        }
+
+        assert_eq!(varint(300), vec![0xAC, 0x02]);
+        assert_eq!(varint(-1).len(), 5);
+    }
+
+    #[test]
+    fn refuses_broken_varints() {
+        assert_eq!(read_varint(&mut [].as_slice()), None);
+        assert_eq!(read_varint(&mut [0x80].as_slice()), None);
+        assert_eq!(read_varint(&mut [0xFF; 6].as_slice()), None);
+    }
+
+    #[test]
+    fn refuses_broken_strings() {
+        let mut too_long = varint(10);
+        too_long.extend_from_slice(b"short");
+        assert_eq!(read_string(&mut too_long.as_slice()), None);
+
+        assert_eq!(read_string(&mut varint(-1).as_slice()), None);
+
+        let mut invalid = varint(2);
+        invalid.extend_from_slice(&[0xC3, 0x28]);
+        assert_eq!(read_string(&mut invalid.as_slice()), None);
+    }
+
+    #[test]
+    fn parses_handshakes() {
+        let parsed = parse_handshake(&handshake("survival1.mc", 1));
+        assert_eq!(parsed, Some(Handshake { protocol: 760, address: String::from("survival1.mc"), next_state: 1 }));
+
+        // Wrong packet ID
+        let mut wrong = handshake("survival1.mc", 1);
+        wrong[0] = 0x01;
+        assert_eq!(parse_handshake(&wrong), None);
+
+        // Cut off within the port
+        let cut = handshake("survival1.mc", 1);
+        assert_eq!(parse_handshake(&cut[..cut.len() - 2]), None);
+        assert_eq!(parse_handshake(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn reads_packets() {
+        let mut frame = varint(3);
+        frame.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(read_packet(&mut frame.as_slice()).await, Some(vec![1, 2, 3]));
+
+        let mut written = vec![];
+        write_packet(&mut written, &[1, 2, 3]).await.unwrap();
+        assert_eq!(written, frame);
+    }
+
+    #[tokio::test]
+    async fn refuses_broken_packets() {
+        // Empty, zero length and cut off
+        assert_eq!(read_packet(&mut [].as_slice()).await, None);
+        assert_eq!(read_packet(&mut varint(0).as_slice()).await, None);
+        assert_eq!(read_packet(&mut [0x05, 1, 2].as_slice()).await, None);
+
+        // Too large or a length that never ends
+        let mut oversized = varint(MAX_PACKET_LENGTH as i32 + 1);
+        oversized.extend(vec![0; MAX_PACKET_LENGTH + 1]);
+        assert_eq!(read_packet(&mut oversized.as_slice()).await, None);
+        assert_eq!(read_packet(&mut [0xFF; 8].as_slice()).await, None);
+        assert_eq!(read_packet(&mut varint(-1).as_slice()).await, None);
+    }
+
+    #[test]
+    fn builds_legacy_responses() {
+        let response = legacy_response("Nobody is hosting right now.");
+        assert_eq!(response[0], 0xFF);
+
+        let length = u16::from_be_bytes([response[1], response[2]]) as usize;
+        assert_eq!(response.len(), 3 + length * 2);
+
+        let text: Vec<u16> = response[3..].chunks(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+        let text = String::from_utf16(&text).unwrap();
+        assert_eq!(text.split('\0').collect::<Vec<_>>(), vec!["§1", "127", "mcsync", "Nobody is hosting right now.", "0", "20"]);
+    }
+
+    #[tokio::test]
+    async fn answers_legacy_pings_without_names() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        client.write_all(&[0xFE, 0x01, 0xFA]).await.unwrap();
+
+        handle_legacy_ping(server).await.unwrap();
+
+        let mut response = vec![];
+        client.read_to_end(&mut response).await.unwrap();
+        assert_eq!(response, legacy_response("Nobody is hosting right now."));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use mcsync_proto::time::now;
+use paris::{info, log, success, warn};
+
+use crate::{database::SharedDatabase, wireguard::{Wireguard, PeerStats}};
+
+/// How often the running interface gets asked for its peers.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Clients send a keepalive every 25 seconds and WireGuard renews its handshake every 2 minutes,
+/// so a handshake older than this means the tunnel is down. (REJECT_AFTER_TIME in WireGuard's whitepaper)
+const ONLINE_TIMEOUT: u64 = 180;
+
+/// Derives who is online from WireGuard itself, so we don't depend on clients talking to the backend.
+pub struct Presence {
+    wireguard: Arc<Wireguard>,
+    database: SharedDatabase,
+
+    /// Received bytes per public key during the last poll.
+    rx_bytes: HashMap<String, u64>
+}
+
+impl Presence {
+    pub fn new(wireguard: Arc<Wireguard>, database: SharedDatabase) -> Self {
+        Self { wireguard, database, rx_bytes: HashMap::new() }
+    }
+
+    pub async fn track(mut self) {
+        info!("Track presence of clients every {} seconds", POLL_INTERVAL.as_secs());
+
+        let mut failing = false;
+
+        loop {
+            let wireguard = self.wireguard.clone();
+
+            // Talking to the socket blocks, even though it's quick.
+            match tokio::task::spawn_blocking(move || wireguard.peers()).await {
+                Ok(Ok(peers)) => {
+                    if failing {
+                        success!("Presence tracking works again.");
+                        failing = false;
+                    }
+
+                    self.update(peers);
+                },
+                Ok(Err(error)) => {
+                    // Only complain once instead of every few seconds.
+                    if !failing {
+                        warn!("Cannot track presence: {}", error);
+                        failing = true;
+                    }
+                },
+                Err(error) => {
+                    warn!("Presence poll crashed: {}", error);
+                }
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Presence changes all the time, so it's stored by `database::sync_storage` within a few seconds.
+    fn update(&mut self, peers: Vec<PeerStats>) {
+        let mut db = self.database.lock().unwrap();
+
+        for peer in peers {
+            let previous_rx = self.rx_bytes.insert(peer.public_key.clone(), peer.rx_bytes);
+
+            let client = match db.get_client_by_public_key(&peer.public_key) {
+                Some(c) => c.clone(),
+                None => continue
+            };
+
+            // Received something since the last poll? Then they are here right now.
+            let seen = match previous_rx {
+                Some(rx) if peer.rx_bytes > rx => now(),
+                _ => peer.last_handshake
+            };
+
+            let online = seen + ONLINE_TIMEOUT > now();
+
+            if db.set_presence(&peer.public_key, seen, online) && online != client.online {
+                log!("{} is now {}", client.name, if online { "online" } else { "offline" });
+            }
+        }
+    }
+}
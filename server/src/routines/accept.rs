@@ -1,17 +1,17 @@
 use std::{io::Read};
 use std::process::exit;
 
-use domain::utils::base64;
 use ipnet::Ipv4Net;
+use mcsync_proto::info::{ClientInfo, ServerInfo};
 use paris::{error};
-use rand::{RngCore, Rng};
-use reqwest::Url;
-use serde::{Deserialize, Serialize};
+use rand::Rng;
 use std::io::{self};
 
 use crate::database::{Database, DatabaseClient};
 use crate::docker::DockerManager;
 use crate::env;
+use crate::routines::server_info;
+use crate::signing;
 use crate::wireguard::Wireguard;
 
 pub struct Accept<'a> {
@@ -20,24 +20,6 @@ pub struct Accept<'a> {
     docker: &'a DockerManager
 }
 
-#[derive(Debug, Deserialize)]
-struct ClientInfo {
-    version: u16,
-    wireguard_pub: String
-}
-
-#[derive(Debug, Serialize)]
-struct ServerInfo {
-    version: u16,
-    endpoint: String,
-    public_key: String,
-    psk: String,
-    tool_subnet: String,
-    user_subnet: String,
-    ipv4_address: String,
-    dns: String
-}
-
 impl<'a> Accept<'a> {
     pub fn new(database: &'a mut Database, wireguard: &'a mut Wireguard, docker: &'a DockerManager) -> Self {
         Self { database, wireguard, docker }
@@ -88,50 +70,18 @@ impl<'a> Accept<'a> {
         names[rand::thread_rng().gen_range(0..names.len() - 1)].to_string()
     }
 
-    pub async fn execute(&mut self) {
-        let endpoint: String = match std::env::var("ENDPOINT") {
-            Ok(endpoint) => {
-                // We just pretent it's a http URL.
-                match Url::parse(&format!("http://{}", endpoint)) {
-                    Ok(result) => {
-                        if result.port().is_none() {
-                            error!("You need to set a port in ENDPOINT. If you're not sure use 51820 as it's WireGuard's default port.");
-                            exit(1);
-                        }
-
-                        endpoint
-                    },
-                    Err(error) => {
-                        error!("Environment variable contains invalid address: {}", error);
-                        exit(1);
-                    }
-                }
-            },
-            Err(_) => {
-                error!("You need to set the environment variable ENDPOINT which is an address ( [Domain OR IPv4]:PORT ) under which WireGuard is reachable by others.");
-                exit(1);
-            }
-        };
-
-        let dns_ip = match self.docker.get_dns_container().await {
-            Some(dns) => {
-                self.docker
-                    .get_container_ip(dns)
-                    .await
-                    .unwrap()
-            },
-            None => {
-                error!("Cannot find DNS container. Did you rename your containers? The name has to contain \"dns\" somewhere e.g. \"mcsync-dns-1\".");
-                exit(1);
-            }
-        };
-
-        let args: Vec<String> = std::env::args().collect();
-        let client_name = match args.get(2) {
-            Some(cn) => cn.to_owned(),
+    /// `fingerprint` is what the client sees when running "mcsync client-info". If set, the client info has to match it.
+    pub async fn execute(&mut self, client_name: Option<String>, fingerprint: Option<String>) {
+        let client_name = match client_name {
+            Some(cn) => cn,
             None => self.random_name(),
         };
 
+        if self.database.get_client_by_name(&client_name).is_some() || self.database.owns_syncs(&client_name) {
+            error!("There is already a client or game server owner called \"{}\". Choose another name.", client_name);
+            exit(1);
+        }
+
         let mut contents: Vec<u8> = Vec::new();
         let stdin = io::stdin();
         let mut handle = stdin.lock();
@@ -146,107 +96,105 @@ impl<'a> Accept<'a> {
 
         let unparsed = std::str::from_utf8(&contents).unwrap();
 
-        let parsed = match serde_json::from_str::<ClientInfo>(&unparsed) {
+        let parsed = match serde_json::from_str::<ClientInfo>(unparsed) {
             Ok(o) => o,
             Err(error) => {
-                println!("JSON: {}", &unparsed);
                 error!("Invalid json syntax: {}", error);
                 std::process::exit(1);
             }
         };
 
-        if parsed.version > 1 {
+        if !parsed.is_supported() {
             error!("User info has been created using a newer format.");
             exit(1);
         }
 
-        let net: Ipv4Net = env::get_user_subnet();
-        let mut ipv4: Option<String> = None;
-
-        let ip_space = net.hosts();
-
-        // Loop over available IP space to find a free address.
-        // This is like a mini DHCP, without DHCP protocol.
-
-        // .skip(1): This skips the first usable address since that one is reserved for the VPN itself.
-        for available_ip in ip_space.skip(1) {
-            let mut available = true;
-            for client in self.database.get_clients() {
-                if client.ipv4_address == available_ip.to_string() {
-                    available = false;
-                    break;
-                }
-            }
+        if wireguard_keys::Pubkey::from_base64(&parsed.wireguard_pub).is_err() {
+            error!("Client info contains an invalid WireGuard public key.");
+            exit(1);
+        }
 
-            if available {
-                ipv4 = Some(available_ip.to_string());
-                break;
-            }
+        if let Some(client) = self.database.get_client_by_public_key(&parsed.wireguard_pub) {
+            error!("This client has already been accepted as \"{}\". Use \"mcsync-server server-info\" instead.", client.name);
+            exit(1);
         }
 
-        if ipv4.is_none() {
-            error!(
-                "No more space in network {}! Cannot add any new client. Consider remove inactive clients using \"mcsync-server remove [CLIENT_NAME]\"",
-                &net.to_string()
-            );
-            return;
+        // Stdout is reserved for the server info, so everything goes to stderr.
+        match (signing::verify_client_info(&parsed), fingerprint) {
+            (Err(error), _) => {
+                error!("{}", error);
+                exit(1);
+            },
+            (Ok(Some(actual)), Some(expected)) if !actual.eq_ignore_ascii_case(expected.trim()) => {
+                error!("Fingerprint of client info is {} but you expected {}. Don't accept it!", actual, expected);
+                exit(1);
+            },
+            (Ok(Some(actual)), _) => {
+                eprintln!("Client fingerprint: {}", actual);
+            },
+            (Ok(None), Some(_)) => {
+                error!("Client info isn't signed, so its fingerprint cannot be checked. Ask them to update mcsync.");
+                exit(1);
+            },
+            (Ok(None), None) => {
+                error!("Client info isn't signed. Make sure it really comes from who you think it does.");
+            }
         }
 
-        // Generate random PSK
-        let mut rng = rand::thread_rng();
-        let mut psk: [u8; 32] = [0; 32];
+        let server_info = self.admit(&client_name, &parsed.wireguard_pub).await;
 
-        // Fill psk array with random bytes.
-        rng.fill_bytes(&mut psk);
+        println!("{}", serde_json::to_string_pretty(&server_info).unwrap());
+    }
 
-        let psk_base64 = base64::encode_string(&psk);
-        let address = ipv4.unwrap();
+    /// Assigns an IP address to a new client and adds it to WireGuard. Exits if that's not possible.
+    pub async fn admit(&mut self, client_name: &str, wireguard_pub: &str) -> ServerInfo {
+        let address = match allocate_address(self.database) {
+            Some(a) => a,
+            None => {
+                error!(
+                    "No more space in network {}! Cannot add any new client. Consider remove inactive clients using \"mcsync-server remove [CLIENT_NAME]\"",
+                    env::get_user_subnet()
+                );
+                exit(1);
+            }
+        };
 
         let client = DatabaseClient {
-            name: client_name.clone(),
-            ipv4_address: address.clone(),
+            name: client_name.to_string(),
+            ipv4_address: address,
             last_seen: 0,
-            wg_public_key: parsed.wireguard_pub.clone(),
-            wg_psk: psk_base64.clone()
+            wg_public_key: wireguard_pub.to_string(),
+            wg_psk: Wireguard::generate_psk(),
+            online: false
         };
 
-        self.database.new_client(client.clone());
-        self.wireguard.add_peer(parsed.wireguard_pub, psk_base64, address.clone());
-
-        let public_key = self.database.get_wireguard_private_key().pubkey().to_base64();
+        // Collect everything first, so we don't end up with a half accepted client.
+        let server_info = server_info::build(self.database, self.docker, &client).await;
 
-        let tool_subnet = match self.docker.get_network().await {
-            Ok(info) => {
-                // Like this is really dangerous. There are a lot of .unwrap's but you only live once, right?
-                info.ipam.unwrap().config.unwrap().get(0).unwrap().subnet.clone().unwrap()
-            },
-            Err(error) => {
-                error!("Cannot find Docker network \"mcsync\": {}", error);
-                exit(1);
-            }
-        };
+        self.database.new_client(client.clone());
+        self.wireguard.regenerate_config(self.database);
 
-        let server_info = ServerInfo {
-            version: 1,
-            endpoint,
-            public_key,
-            psk: client.wg_psk,
-            ipv4_address: address,
-            user_subnet: net.to_string(),
-            tool_subnet,
-            dns: dns_ip
-        };
+        // Only restart WireGuard (and disconnect everyone) if the peer cannot be added at runtime.
+        // Stdout is reserved for the server info, so complain on stderr.
+        if self.wireguard.apply_peer(&client.wg_public_key, &client.wg_psk, &client.ipv4_address).is_none() {
+            error!("Couldn't add peer at runtime. Restart WireGuard instead ...");
 
-        match self.docker.get_vpn_container().await {
-            Some(vpn) => {
-                let _ = self.docker.restart_container(vpn);
-            },
-            None => {
-                error!("Cannot find WireGuard container. Is it stopped?");
+            if self.wireguard.restart(self.docker).await.is_none() {
                 exit(1);
             }
         }
 
-        println!("{}", serde_json::to_string_pretty(&server_info).unwrap());
+        server_info
     }
 }
+
+/// Finds a free address in the user subnet. This is like a mini DHCP, without DHCP protocol.
+pub fn allocate_address(database: &Database) -> Option<String> {
+    let net: Ipv4Net = env::get_user_subnet();
+
+    // .skip(1): This skips the first usable address since that one is reserved for the VPN itself.
+    net.hosts()
+        .skip(1)
+        .map(|ip| ip.to_string())
+        .find(|ip| database.get_client_by_ip(ip).is_none())
+}
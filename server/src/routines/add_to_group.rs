@@ -0,0 +1,34 @@
+use std::process::exit;
+
+use paris::{error, success};
+
+use crate::database::Database;
+
+pub struct AddToGroup<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> AddToGroup<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&mut self, group: &str, client_name: &str) {
+        if group.trim().is_empty() {
+            error!("The name of the group cannot be empty.");
+            exit(1);
+        }
+
+        if self.database.get_client_by_name(client_name).is_none() {
+            error!("Cannot find user \"{}\"", client_name);
+            exit(1);
+        }
+
+        if !self.database.add_to_group(group, client_name) {
+            error!("{} already is in group {}.", client_name, group);
+            exit(1);
+        }
+
+        success!("Added {} to group {}", client_name, group);
+    }
+}
@@ -0,0 +1,49 @@
+use std::process::exit;
+
+use paris::{error, success};
+
+use crate::database::Database;
+use crate::docker::DockerManager;
+use crate::routines::accept::Accept;
+use crate::wireguard::Wireguard;
+
+pub struct Approve<'a> {
+    database: &'a mut Database,
+    wireguard: &'a mut Wireguard,
+    docker: &'a DockerManager
+}
+
+impl<'a> Approve<'a> {
+    pub fn new(database: &'a mut Database, wireguard: &'a mut Wireguard, docker: &'a DockerManager) -> Self {
+        Self { database, wireguard, docker }
+    }
+
+    /// Accepts a join request. The client picks up its server info the next time it polls.
+    pub async fn execute(&mut self, client_name: &str) {
+        let pending = match self.database.get_pending_by_name(client_name) {
+            Some(p) => p.clone(),
+            None => {
+                error!("Nobody called \"{}\" asked to join. See \"mcsync-server list-pending\".", client_name);
+                exit(1);
+            }
+        };
+
+        if pending.server_info.is_some() {
+            error!("{} has already been approved but didn't pick up their server info yet.", client_name);
+            exit(1);
+        }
+
+        if self.database.get_client_by_name(client_name).is_some() || self.database.owns_syncs(client_name) {
+            error!("There is already a client or game server owner called \"{}\". Deny this request instead.", client_name);
+            exit(1);
+        }
+
+        let server_info = Accept::new(self.database, self.wireguard, self.docker)
+            .admit(&pending.name, &pending.wg_public_key)
+            .await;
+
+        self.database.approve_pending(client_name, serde_json::to_value(&server_info).unwrap());
+
+        success!("Approved {}. They are connected as {} once \"mcsync join\" picked up their server info.", client_name, server_info.ipv4_address);
+    }
+}
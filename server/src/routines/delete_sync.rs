@@ -0,0 +1,52 @@
+use std::process::exit;
+
+use paris::{error, success, warn};
+
+use crate::{database::Database, dns::DNSManager, docker::DockerManager, manifest};
+
+pub struct DeleteSync<'a> {
+    database: &'a mut Database,
+    docker: &'a DockerManager
+}
+
+impl<'a> DeleteSync<'a> {
+    pub fn new(database: &'a mut Database, docker: &'a DockerManager) -> Self {
+        Self { database, docker }
+    }
+
+    /// Deletes a game server including all of its files on the backend. Local copies of members stay untouched.
+    pub async fn execute(&mut self, sync_name: &str, force: bool) {
+        let sync = match self.database.get_sync_by_name(sync_name) {
+            Some(s) => s.clone(),
+            None => {
+                error!("There is no game server called {}.", sync_name);
+                exit(1);
+            }
+        };
+
+        if self.database.get_lease(&sync.id).is_some() && !force {
+            error!("{} is being hosted right now. Use --force to delete it anyway.", sync_name);
+            exit(1);
+        }
+
+        self.database.remove_sync(sync_name);
+        if self.database.flush().is_none() {
+            error!("Couldn't save the database, so {} hasn't been deleted.", sync_name);
+            exit(1);
+        }
+
+        let sync_dir = manifest::sync_dir(&sync.id);
+        if sync_dir.exists() {
+            if let Err(error) = std::fs::remove_dir_all(&sync_dir) {
+                warn!("Couldn't delete files at {}: {}", sync_dir.display(), error);
+            }
+        }
+
+        let dns_manager = DNSManager::new(self.docker.clone());
+        if dns_manager.remove_record(sync_name).is_some() {
+            dns_manager.restart_dns().await;
+        }
+
+        success!("Deleted {} ({})", sync_name, sync.id);
+    }
+}
@@ -0,0 +1,33 @@
+use std::process::exit;
+
+use paris::{error, success};
+
+use crate::database::Database;
+
+pub struct Deny<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> Deny<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&mut self, client_name: &str) {
+        match self.database.get_pending_by_name(client_name) {
+            Some(p) if p.server_info.is_some() => {
+                error!("{} has already been approved. Use \"mcsync-server remove {}\" instead.", client_name, client_name);
+                exit(1);
+            },
+            Some(_) => {},
+            None => {
+                error!("Nobody called \"{}\" asked to join.", client_name);
+                exit(1);
+            }
+        }
+
+        self.database.remove_pending(client_name);
+
+        success!("Denied join request of {}", client_name);
+    }
+}
@@ -0,0 +1,58 @@
+use std::process::exit;
+
+use paris::{error, success, warn};
+
+use crate::database::{Database, Role, Subject};
+
+pub struct Grant<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> Grant<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    /// Without client and group, this sets the role of everyone else.
+    pub fn execute(&mut self, sync_name: &str, role: Role, client: Option<String>, group: Option<String>) {
+        if self.database.get_sync_by_name(sync_name).is_none() {
+            error!("Cannot find game server \"{}\"", sync_name);
+            exit(1);
+        }
+
+        if role == Role::Owner {
+            error!("The client that created a game server is its owner. Grant host instead.");
+            exit(1);
+        }
+
+        let subject = match (client, group) {
+            (Some(client), _) => {
+                if self.database.get_client_by_name(&client).is_none() {
+                    error!("Cannot find user \"{}\"", client);
+                    exit(1);
+                }
+
+                if self.database.get_sync_by_name(sync_name).map(|s| s.owner == client).unwrap_or(false) {
+                    warn!("{} owns {}, so this has no effect until someone else owns it.", client, sync_name);
+                }
+
+                Subject::Client(client)
+            },
+            (None, Some(group)) => {
+                if !self.database.get_groups().iter().any(|g| g.name == group) {
+                    warn!("Group {} has no members yet. Add some using add-to-group.", group);
+                }
+
+                Subject::Group(group)
+            },
+            (None, None) => {
+                self.database.set_default_role(sync_name, role);
+                success!("Everyone without another grant is {} of {} now.", role, sync_name);
+                return;
+            }
+        };
+
+        self.database.grant(sync_name, subject.clone(), role);
+        success!("{} is {} of {} now.", subject, role, sync_name);
+    }
+}
@@ -0,0 +1,57 @@
+use std::{path::Path, process::exit};
+
+use paris::{error, info, success};
+
+use serde_json::Value;
+
+use crate::{database::Database, env, storage::{migration, Backend}, utils::file};
+
+pub struct ImportDatabase<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> ImportDatabase<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    /// Moves an existing database.json into the configured storage, keys included. Runs once when switching to SQLite.
+    pub fn execute(&mut self, path: &str, force: bool) {
+        if env::get_database_backend() == Backend::Json {
+            error!("The database already is a .json file. Set DATABASE_BACKEND=sqlite to import it into SQLite.");
+            exit(1);
+        }
+
+        if !self.database.is_empty() && !force {
+            error!("The database isn't empty. Use --force to replace everything in it.");
+            exit(1);
+        }
+
+        let value = match file::read_json::<Value>(Path::new(path)) {
+            Ok(Some((value, _))) => value,
+            Ok(None) => {
+                error!("There is no database at {}", path);
+                exit(1);
+            },
+            Err(error) => {
+                error!("Couldn't read {}: {}", path, error);
+                exit(1);
+            }
+        };
+
+        // Older databases are upgraded on the way. The file itself stays as it is.
+        let data = match migration::upgrade(value) {
+            Ok((data, _)) => data,
+            Err(error) => {
+                error!("Couldn't read {}: {}", path, error);
+                exit(1);
+            }
+        };
+
+        let (clients, syncs) = (data.client.len(), data.synced.len());
+        self.database.import(data);
+
+        success!("Imported {} clients and {} game servers from {}.", clients, syncs, path);
+        info!("Restart the backend, so WireGuard picks up the imported keys. Keep {} as a backup.", path);
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt::Write;
+
+use mcsync_proto::time::now;
+use paris::{info, success};
+use rand::RngCore;
+
+use crate::database::{Database, DatabaseInvite};
+use crate::env;
+use crate::signing::Identity;
+
+pub struct Invite<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> Invite<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    /// Mints a token that lets `uses` people join within `expires` seconds without approval.
+    pub fn execute(&mut self, uses: u32, expires: u64) {
+        let token = generate_token(16);
+
+        self.database.new_invite(DatabaseInvite {
+            token: token.clone(),
+            uses_left: uses,
+            created: now(),
+            expires: now() + expires
+        });
+
+        success!("Created invite for {} {}. Share this command:", uses, if uses == 1 { "person" } else { "people" });
+        println!(
+            "\n    mcsync join {} {} --fingerprint {}\n",
+            join_address(), token, Identity::from_database(self.database).fingerprint()
+        );
+        info!("Revoke it using \"mcsync-server revoke-invite {}\"", token);
+    }
+}
+
+/// Random hex string out of `length` bytes.
+pub fn generate_token(length: usize) -> String {
+    let mut bytes = vec![0u8; length];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    bytes.iter().fold(String::new(), |mut token, b| {
+        let _ = write!(token, "{:02x}", b);
+        token
+    })
+}
+
+/// Address of the enrollment listener as friends reach it. The port is omitted if it's the default one.
+fn join_address() -> String {
+    let endpoint = env::get_endpoint();
+    let host = endpoint.rsplit_once(':').map(|(host, _)| host).unwrap_or(&endpoint);
+
+    match env::get_enrollment_port() {
+        8081 => host.to_string(),
+        port => format!("{}:{}", host, port)
+    }
+}
@@ -0,0 +1,83 @@
+use mcsync_proto::time::format_ago;
+use paris::info;
+use serde::Serialize;
+
+use crate::database::{Database, DatabaseClient};
+
+/// How clients are presented to admins. The preshared key stays secret.
+#[derive(Serialize)]
+pub struct ClientEntry {
+    pub(crate) name: String,
+    pub(crate) ipv4_address: String,
+    pub(crate) wg_public_key: String,
+    pub(crate) online: bool,
+    pub(crate) last_seen: u64,
+
+    /// Game servers this client is hosting right now.
+    pub(crate) hosting: Vec<String>,
+
+    /// Game servers this client created.
+    pub(crate) owns: Vec<String>
+}
+
+impl ClientEntry {
+    pub fn new(database: &Database, client: &DatabaseClient) -> Self {
+        let syncs = database.get_syncs();
+
+        Self {
+            name: client.name.clone(),
+            ipv4_address: client.ipv4_address.clone(),
+            wg_public_key: client.wg_public_key.clone(),
+            online: client.online,
+            last_seen: client.last_seen,
+            hosting: syncs.iter()
+                .filter(|s| database.holds_lease(&s.id, &client.ipv4_address))
+                .map(|s| s.name.clone())
+                .collect(),
+            owns: syncs.iter()
+                .filter(|s| s.owner == client.name)
+                .map(|s| s.name.clone())
+                .collect()
+        }
+    }
+
+    pub fn presence(&self) -> String {
+        if self.online {
+            String::from("online")
+        } else if self.last_seen == 0 {
+            String::from("never seen")
+        } else {
+            format!("last seen {}", format_ago(self.last_seen))
+        }
+    }
+}
+
+pub struct ListClients<'a> {
+    database: &'a Database
+}
+
+impl<'a> ListClients<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&self, json: bool) {
+        let clients: Vec<ClientEntry> = self.database.get_clients().iter()
+            .map(|c| ClientEntry::new(self.database, c))
+            .collect();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&clients).unwrap());
+            return;
+        }
+
+        if clients.is_empty() {
+            info!("Nobody has been accepted yet.");
+            return;
+        }
+
+        for client in clients {
+            println!("{} [{}] ({})", client.name, client.ipv4_address, client.presence());
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use paris::info;
+
+use crate::database::Database;
+
+pub struct ListGroups<'a> {
+    database: &'a Database
+}
+
+impl<'a> ListGroups<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&self, json: bool) {
+        let groups = self.database.get_groups();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&groups).unwrap());
+            return;
+        }
+
+        if groups.is_empty() {
+            info!("There are no groups yet. Create one using add-to-group.");
+            return;
+        }
+
+        for group in groups {
+            println!("{}: {}", group.name, group.members.join(", "));
+        }
+    }
+}
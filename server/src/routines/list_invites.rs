@@ -0,0 +1,36 @@
+use mcsync_proto::time::{format_ago, format_in};
+use paris::info;
+
+use crate::database::Database;
+
+pub struct ListInvites<'a> {
+    database: &'a Database
+}
+
+impl<'a> ListInvites<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&self, json: bool) {
+        let invites = self.database.get_invites();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&invites).unwrap());
+            return;
+        }
+
+        if invites.is_empty() {
+            info!("There are no open invites.");
+            return;
+        }
+
+        for invite in invites {
+            println!(
+                "{} ({} {} left, created {}, expires {})",
+                invite.token, invite.uses_left, if invite.uses_left == 1 { "use" } else { "uses" },
+                format_ago(invite.created), format_in(invite.expires)
+            );
+        }
+    }
+}
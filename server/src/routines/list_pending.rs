@@ -0,0 +1,67 @@
+use mcsync_proto::time::format_ago;
+use paris::info;
+use serde::Serialize;
+
+use crate::database::{Database, DatabasePending};
+
+/// How join requests are presented to admins. The token stays secret.
+#[derive(Serialize)]
+pub struct PendingEntry {
+    pub(crate) name: String,
+    pub(crate) wg_public_key: String,
+    pub(crate) address: String,
+    pub(crate) fingerprint: String,
+    pub(crate) requested: u64,
+    pub(crate) approved: bool
+}
+
+impl PendingEntry {
+    pub fn new(pending: &DatabasePending) -> Self {
+        Self {
+            name: pending.name.clone(),
+            wg_public_key: pending.wg_public_key.clone(),
+            address: pending.address.clone(),
+            fingerprint: pending.fingerprint.clone(),
+            requested: pending.requested,
+            approved: pending.server_info.is_some()
+        }
+    }
+}
+
+pub struct ListPending<'a> {
+    database: &'a Database
+}
+
+impl<'a> ListPending<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&self, json: bool) {
+        let pending: Vec<PendingEntry> = self.database.get_pending().iter()
+            .map(PendingEntry::new)
+            .collect();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&pending).unwrap());
+            return;
+        }
+
+        if pending.is_empty() {
+            info!("Nobody asked to join.");
+            return;
+        }
+
+        for entry in pending {
+            println!(
+                "{} from {} (asked {}{})",
+                entry.name, entry.address, format_ago(entry.requested),
+                if entry.approved { ", approved" } else { "" }
+            );
+            println!(
+                "  Fingerprint: {}",
+                if entry.fingerprint.is_empty() { "none, client info isn't signed" } else { &entry.fingerprint }
+            );
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use mcsync_proto::time::format_ago;
+use paris::info;
+use serde::Serialize;
+
+use crate::{database::{Database, DatabaseSynced, Role}, manifest};
+
+#[derive(Serialize)]
+pub struct SyncEntry {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) owner: String,
+
+    /// Role of everyone without a grant.
+    pub(crate) default_role: Role,
+    pub(crate) start_file: String,
+    pub(crate) created: u64,
+    pub(crate) generation: u64,
+
+    /// Name of the client hosting it right now.
+    pub(crate) host: Option<String>,
+    pub(crate) last_host: String,
+    pub(crate) last_hosted: u64
+}
+
+impl SyncEntry {
+    pub fn new(database: &Database, sync: &DatabaseSynced) -> Self {
+        Self {
+            id: sync.id.clone(),
+            name: sync.name.clone(),
+            owner: sync.owner.clone(),
+            default_role: sync.default_role,
+            start_file: sync.start_file.clone(),
+            created: sync.created,
+            generation: manifest::load(&sync.id).map(|m| m.generation).unwrap_or(0),
+            host: database.get_lease(&sync.id)
+                .map(|l| database.get_client_by_ip(&l.holder).map(|c| c.name.clone()).unwrap_or_else(|| l.holder.clone())),
+            last_host: sync.last_host.clone(),
+            last_hosted: sync.last_hosted
+        }
+    }
+}
+
+pub struct ListSyncs<'a> {
+    database: &'a Database
+}
+
+impl<'a> ListSyncs<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&self, json: bool) {
+        let syncs: Vec<SyncEntry> = self.database.get_syncs().iter()
+            .map(|s| SyncEntry::new(self.database, s))
+            .collect();
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&syncs).unwrap());
+            return;
+        }
+
+        if syncs.is_empty() {
+            info!("There are no game servers yet.");
+            return;
+        }
+
+        for sync in syncs {
+            let state = match &sync.host {
+                Some(host) => format!("hosted by {}", host),
+                None if sync.last_host.is_empty() => String::from("never hosted"),
+                None => format!("last hosted by {} {}", sync.last_host, format_ago(sync.last_hosted))
+            };
+
+            let owner = if sync.owner.is_empty() { "nobody" } else { &sync.owner };
+            println!("{} ({}) - owned by {}, others are {}, generation {}, {}", sync.name, sync.id, owner, sync.default_role, sync.generation, state);
+        }
+    }
+}
@@ -1,2 +1,22 @@
 pub mod accept;
-pub mod remove;
\ No newline at end of file
+pub mod remove;
+pub mod status;
+pub mod list_clients;
+pub mod show_client;
+pub mod rename_client;
+pub mod list_syncs;
+pub mod delete_sync;
+pub mod server_info;
+pub mod approve;
+pub mod deny;
+pub mod list_pending;
+pub mod invite;
+pub mod list_invites;
+pub mod revoke_invite;
+pub mod import_database;
+pub mod grant;
+pub mod revoke;
+pub mod add_to_group;
+pub mod remove_from_group;
+pub mod list_groups;
+pub mod show_permissions;
@@ -1,33 +1,61 @@
-use paris::{error, success};
+use std::process::exit;
+
+use paris::{error, info, success, warn};
 
 use crate::database::Database;
+use crate::dns::DNSManager;
+use crate::docker::DockerManager;
+use crate::wireguard::Wireguard;
 
 pub struct RemoveUser<'a> {
     database: &'a mut Database,
+    wireguard: &'a mut Wireguard,
+    docker: &'a DockerManager
 }
 
 impl<'a> RemoveUser<'a> {
-    pub fn new(database: &'a mut Database) -> Self {
-        Self { database }
+    pub fn new(database: &'a mut Database, wireguard: &'a mut Wireguard, docker: &'a DockerManager) -> Self {
+        Self { database, wireguard, docker }
     }
 
-    pub fn execute(&mut self) {
-        let args: Vec<String> = std::env::args().collect();
-        let client_name = match args.get(2) {
-            Some(cn) => cn.to_owned(),
+    /// Removes a client and revokes its access immediately: The VPN peer is gone and leases are released.
+    pub async fn execute(&mut self, client_name: &str) {
+        let client = match self.database.remove_client(client_name) {
+            Some(client) => client,
             None => {
-                error!("No username specified!");
-                return;
+                error!("Cannot find user \"{}\"", client_name);
+                exit(1);
             }
         };
 
-        match self.database.remove_client(&client_name) {
-            Some(client) => {
-                success!("Removed {} ({})", client.name, client.ipv4_address);
-            },
-            None => {
-                error!("Cannot find user \"{}\"", client_name);
+        let revoked_leases = self.database.revoke_leases(&client.ipv4_address);
+        if self.database.flush().is_none() {
+            error!("Couldn't save the database, so {} hasn't been removed.", client.name);
+            exit(1);
+        }
+
+        // The config file keeps the peer away after restarts, the live removal cuts the tunnel right now.
+        self.wireguard.regenerate_config(self.database);
+
+        if self.wireguard.remove_live_peer(&client.wg_public_key).is_none() {
+            warn!("Couldn't remove peer at runtime. Restart WireGuard instead ...");
+
+            if self.wireguard.restart(self.docker).await.is_none() {
+                error!("{} may still be connected until WireGuard is restarted.", client.name);
+                exit(1);
             }
         }
+
+        if !revoked_leases.is_empty() {
+            let mut dns_manager = DNSManager::new(self.docker.clone());
+            dns_manager.setup_service_domains().await;
+
+            for name in &revoked_leases {
+                info!("{} no longer hosts {}", client.name, name);
+                dns_manager.point_game_server(name, None).await;
+            }
+        }
+
+        success!("Removed {} and revoked their VPN access. {} is free again.", client.name, client.ipv4_address);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,24 @@
+use std::process::exit;
+
+use paris::{error, success};
+
+use crate::database::Database;
+
+pub struct RemoveFromGroup<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> RemoveFromGroup<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&mut self, group: &str, client_name: &str) {
+        if !self.database.remove_from_group(group, client_name) {
+            error!("{} isn't in group {}.", client_name, group);
+            exit(1);
+        }
+
+        success!("Removed {} from group {}", client_name, group);
+    }
+}
@@ -0,0 +1,34 @@
+use std::process::exit;
+
+use paris::{error, success};
+
+use crate::database::Database;
+
+pub struct RenameClient<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> RenameClient<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&mut self, client_name: &str, new_name: &str) {
+        if new_name.trim().is_empty() {
+            error!("The new name cannot be empty.");
+            exit(1);
+        }
+
+        if self.database.get_client_by_name(client_name).is_none() {
+            error!("Cannot find user \"{}\"", client_name);
+            exit(1);
+        }
+
+        if self.database.rename_client(client_name, new_name).is_none() {
+            error!("There is already a client or game server owner called \"{}\".", new_name);
+            exit(1);
+        }
+
+        success!("Renamed {} to {}", client_name, new_name);
+    }
+}
@@ -0,0 +1,39 @@
+use std::process::exit;
+
+use paris::{error, success};
+
+use crate::database::{Database, Subject};
+
+pub struct Revoke<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> Revoke<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    /// Afterwards, the grants of the client's groups or the default role of the game server apply again.
+    pub fn execute(&mut self, sync_name: &str, client: Option<String>, group: Option<String>) {
+        if self.database.get_sync_by_name(sync_name).is_none() {
+            error!("Cannot find game server \"{}\"", sync_name);
+            exit(1);
+        }
+
+        let subject = match (client, group) {
+            (Some(client), _) => Subject::Client(client),
+            (None, Some(group)) => Subject::Group(group),
+            (None, None) => {
+                error!("Tell whose role to revoke using --client or --group.");
+                exit(1);
+            }
+        };
+
+        if !self.database.revoke(sync_name, &subject) {
+            error!("{} has no role on {}.", subject, sync_name);
+            exit(1);
+        }
+
+        success!("Revoked the role of {} on {}", subject, sync_name);
+    }
+}
@@ -0,0 +1,24 @@
+use std::process::exit;
+
+use paris::{error, success};
+
+use crate::database::Database;
+
+pub struct RevokeInvite<'a> {
+    database: &'a mut Database
+}
+
+impl<'a> RevokeInvite<'a> {
+    pub fn new(database: &'a mut Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&mut self, token: &str) {
+        if self.database.revoke_invite(token).is_none() {
+            error!("There is no invite {}. See \"mcsync-server list-invites\".", token);
+            exit(1);
+        }
+
+        success!("Revoked invite {}. Nobody can join using it anymore.", token);
+    }
+}
@@ -0,0 +1,131 @@
+use std::process::exit;
+
+use mcsync_proto::info::{ServerInfo, SERVER_INFO_VERSION};
+use paris::error;
+
+use crate::database::{Database, DatabaseClient};
+use crate::docker::DockerManager;
+use crate::env;
+use crate::signing::Identity;
+use crate::wireguard::Wireguard;
+
+/// Collects the server info for `client`. Exits if Docker cannot tell us about our own services.
+pub async fn build(database: &Database, docker: &DockerManager, client: &DatabaseClient) -> ServerInfo {
+    let public_key = database.get_wireguard_private_key().pubkey().to_base64();
+
+    match collect(public_key, &Identity::from_database(database), docker, client).await {
+        Some(s) => s,
+        None => exit(1)
+    }
+}
+
+/// Like `build` but doesn't need the database, so the backend can call it without holding the lock.
+pub async fn collect(public_key: String, identity: &Identity, docker: &DockerManager, client: &DatabaseClient) -> Option<ServerInfo> {
+    let dns = match docker.get_dns_container().await {
+        Some(dns) => docker.get_container_ip(dns).await?,
+        None => {
+            error!("Cannot find DNS container. Did you rename your containers? The name has to contain \"dns\" somewhere e.g. \"mcsync-dns-1\".");
+            return None;
+        }
+    };
+
+    let tool_subnet = match docker.get_network().await {
+        Ok(info) => {
+            let subnet = info.ipam
+                .and_then(|ipam| ipam.config)
+                .and_then(|config| config.first().and_then(|c| c.subnet.clone()));
+
+            match subnet {
+                Some(s) => s,
+                None => {
+                    error!("Docker network \"mcsync\" has no subnet configured.");
+                    return None;
+                }
+            }
+        },
+        Err(error) => {
+            error!("Cannot find Docker network \"mcsync\": {}", error);
+            return None;
+        }
+    };
+
+    let mut server_info = ServerInfo {
+        version: SERVER_INFO_VERSION,
+        endpoint: env::get_endpoint(),
+        public_key,
+        psk: client.wg_psk.clone(),
+        tool_subnet,
+        user_subnet: env::get_user_subnet().to_string(),
+        ipv4_address: client.ipv4_address.clone(),
+        dns,
+        signing_key: Some(identity.public_key()),
+        signature: None
+    };
+    server_info.signature = Some(identity.sign(server_info.signed_message().as_bytes()));
+
+    Some(server_info)
+}
+
+pub struct ShowServerInfo<'a> {
+    database: &'a mut Database,
+    wireguard: &'a mut Wireguard,
+    docker: &'a DockerManager
+}
+
+impl<'a> ShowServerInfo<'a> {
+    pub fn new(database: &'a mut Database, wireguard: &'a mut Wireguard, docker: &'a DockerManager) -> Self {
+        Self { database, wireguard, docker }
+    }
+
+    /// Prints the server info of an existing client again. Their IP address stays the same.
+    /// Stdout is reserved for the server info, so everything else goes to stderr.
+    pub async fn execute(&mut self, client_name: &str, rotate_psk: bool) {
+        let mut client = match self.database.get_client_by_name(client_name) {
+            Some(c) => c.clone(),
+            None => {
+                error!("Cannot find user \"{}\"", client_name);
+                exit(1);
+            }
+        };
+
+        if !rotate_psk {
+            let server_info = build(self.database, self.docker, &client).await;
+            println!("{}", serde_json::to_string_pretty(&server_info).unwrap());
+            return;
+        }
+
+        let old_psk = client.wg_psk.clone();
+        client.wg_psk = Wireguard::generate_psk();
+
+        // Collect everything first, so the old key keeps working if the new server info cannot be created.
+        let server_info = build(self.database, self.docker, &client).await;
+
+        self.database.set_client_psk(client_name, &client.wg_psk);
+        if self.database.flush().is_none() {
+            error!("Couldn't save the new preshared key. {} can continue to use their current server info.", client_name);
+            exit(1);
+        }
+
+        self.wireguard.regenerate_config(self.database);
+
+        // The old key stops working right away. If the new one can't be applied, we stick with the old one.
+        if self.wireguard.apply_peer(&client.wg_public_key, &client.wg_psk, &client.ipv4_address).is_none() {
+            error!("Couldn't rotate key at runtime. Restart WireGuard instead ...");
+
+            if self.wireguard.restart(self.docker).await.is_none() {
+                self.database.set_client_psk(client_name, &old_psk);
+                self.wireguard.regenerate_config(self.database);
+
+                if self.database.flush().is_none() {
+                    error!("Couldn't restore the old preshared key either. Run \"mcsync-server server-info --rotate-psk {}\" again.", client_name);
+                    exit(1);
+                }
+
+                error!("Keep the old preshared key. {} can continue to use their current server info.", client_name);
+                exit(1);
+            }
+        }
+
+        println!("{}", serde_json::to_string_pretty(&server_info).unwrap());
+    }
+}
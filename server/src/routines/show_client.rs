@@ -0,0 +1,39 @@
+use std::process::exit;
+
+use paris::error;
+
+use crate::{database::Database, routines::list_clients::ClientEntry};
+
+pub struct ShowClient<'a> {
+    database: &'a Database
+}
+
+impl<'a> ShowClient<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&self, client_name: &str, json: bool) {
+        let client = match self.database.get_client_by_name(client_name) {
+            Some(c) => ClientEntry::new(self.database, c),
+            None => {
+                error!("Cannot find user \"{}\"", client_name);
+                exit(1);
+            }
+        };
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&client).unwrap());
+            return;
+        }
+
+        let none = String::from("-");
+
+        println!("{}\n{}", client.name, "=".repeat(client.name.chars().count()));
+        println!("IP address:  {}", client.ipv4_address);
+        println!("Public key:  {}", client.wg_public_key);
+        println!("Status:      {}", client.presence());
+        println!("Hosting:     {}", if client.hosting.is_empty() { none.clone() } else { client.hosting.join(", ") });
+        println!("Owns:        {}", if client.owns.is_empty() { none } else { client.owns.join(", ") });
+    }
+}
@@ -0,0 +1,68 @@
+use std::process::exit;
+
+use paris::error;
+use serde::Serialize;
+
+use crate::database::{Database, DatabaseGrant, Role};
+
+#[derive(Serialize)]
+struct EffectiveRole {
+    client: String,
+    role: Role
+}
+
+#[derive(Serialize)]
+struct Permissions {
+    owner: String,
+    default_role: Role,
+    grants: Vec<DatabaseGrant>,
+
+    /// What each client may do after applying grants and groups.
+    clients: Vec<EffectiveRole>
+}
+
+pub struct ShowPermissions<'a> {
+    database: &'a Database
+}
+
+impl<'a> ShowPermissions<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self { database }
+    }
+
+    pub fn execute(&self, sync_name: &str, json: bool) {
+        let sync = match self.database.get_sync_by_name(sync_name) {
+            Some(s) => s,
+            None => {
+                error!("Cannot find game server \"{}\"", sync_name);
+                exit(1);
+            }
+        };
+
+        let permissions = Permissions {
+            owner: sync.owner.clone(),
+            default_role: sync.default_role,
+            grants: sync.grants.clone(),
+            clients: self.database.get_clients().iter()
+                .map(|c| EffectiveRole { client: c.name.clone(), role: self.database.role_of(&sync.id, &c.name) })
+                .collect()
+        };
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&permissions).unwrap());
+            return;
+        }
+
+        println!("Owner: {}", if permissions.owner.is_empty() { "unknown" } else { &permissions.owner });
+        println!("Everyone else: {}", permissions.default_role);
+
+        for grant in &permissions.grants {
+            println!("{}: {}", grant.subject, grant.role);
+        }
+
+        println!();
+        for client in &permissions.clients {
+            println!("{} is {}", client.client, client.role);
+        }
+    }
+}
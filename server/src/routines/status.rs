@@ -0,0 +1,61 @@
+use mcsync_proto::time::format_ago;
+
+use crate::{build, database::Database, http::ep_status::status};
+
+pub struct Status<'a> {
+    database: &'a Database
+}
+
+impl<'a> Status<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        Self { database }
+    }
+
+    /// Prints the same overview members get using `mcsync status`, plus IP addresses.
+    pub fn execute(&self, json: bool) {
+        if json {
+            println!("{}", serde_json::to_string_pretty(&status(self.database)).unwrap());
+            return;
+        }
+
+        let headline = format!("Running mcsync v{}", build::PKG_VERSION);
+        println!("\n{}\n{}\n", headline, "=".repeat(headline.chars().count()));
+
+        println!("Available servers:");
+        let syncs = self.database.get_syncs();
+        if syncs.is_empty() {
+            println!("  There are no game servers yet.");
+        }
+
+        for sync in syncs {
+            let host = self.database.get_lease(&sync.id)
+                .map(|l| self.database.get_client_by_ip(&l.holder).map(|c| c.name.clone()).unwrap_or_else(|| l.holder.clone()));
+
+            match (host, &sync.last_status) {
+                (Some(host), Some(status)) => println!(
+                    "  * {} -> {} / {} players online - {} (hosted by {})",
+                    sync.name, status.online_players, status.max_players, status.version, host
+                ),
+                (Some(host), None) => println!("  * {} (hosted by {})", sync.name, host),
+                (None, _) if sync.last_host.is_empty() => println!("  - {}", sync.name),
+                (None, _) => println!("  - {} (last hosted by {} {})", sync.name, sync.last_host, format_ago(sync.last_hosted))
+            }
+        }
+
+        println!("\nClients:");
+        let clients = self.database.get_clients();
+        if clients.is_empty() {
+            println!("  Nobody has been accepted yet.");
+        }
+
+        for client in clients {
+            if client.online {
+                println!("  * {} [{}] (online)", client.name, client.ipv4_address);
+            } else if client.last_seen == 0 {
+                println!("  - {} [{}] (never seen)", client.name, client.ipv4_address);
+            } else {
+                println!("  - {} [{}] (last seen {})", client.name, client.ipv4_address, format_ago(client.last_seen));
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::process::exit;
+
+use domain::utils::base64;
+use mcsync_proto::{info::ClientInfo, signing::fingerprint};
+use openssl::{pkey::{Id, PKey, Private}, sign::{Signer, Verifier}};
+use paris::error;
+
+use crate::database::Database;
+
+/// Long-term Ed25519 key of this server. Clients use it to make sure a server info really comes from us.
+/// Unlike the WireGuard key it's only used for signing.
+#[derive(Clone)]
+pub struct Identity {
+    key: PKey<Private>
+}
+
+impl Identity {
+    /// Returns a new private key, encoded as it's stored in the database.
+    pub fn generate() -> String {
+        let key = PKey::generate_ed25519().unwrap();
+
+        base64::encode_string(&key.raw_private_key().unwrap())
+    }
+
+    pub fn from_database(database: &Database) -> Self {
+        let key = base64::decode::<Vec<u8>>(database.get_signing_private_key()).ok()
+            .and_then(|raw| PKey::private_key_from_raw_bytes(&raw, Id::ED25519).ok());
+
+        match key {
+            Some(key) => Self { key },
+            None => {
+                error!("Fail to parse signing key. Have you touched it?");
+                exit(1);
+            }
+        }
+    }
+
+    /// Base64 encoded public key, as it's shipped within server infos.
+    pub fn public_key(&self) -> String {
+        base64::encode_string(&self.key.raw_public_key().unwrap())
+    }
+
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.key.raw_public_key().unwrap())
+    }
+
+    /// Returns the base64 encoded signature of `message`.
+    pub fn sign(&self, message: &[u8]) -> String {
+        let mut signer = Signer::new_without_digest(&self.key).unwrap();
+
+        base64::encode_string(&signer.sign_oneshot_to_vec(message).unwrap())
+    }
+}
+
+/// Returns the fingerprint of the client's identity or None if the client info isn't signed.
+pub fn verify_client_info(client_info: &ClientInfo) -> Result<Option<String>, String> {
+    let (signing_key, signature) = match (&client_info.signing_key, &client_info.signature) {
+        (Some(k), Some(s)) => (k, s),
+        _ => return Ok(None)
+    };
+
+    if !verify(signing_key, client_info.signed_message().as_bytes(), signature) {
+        return Err(String::from("Signature of client info is invalid. It has been altered or forged."));
+    }
+
+    let raw = base64::decode::<Vec<u8>>(signing_key).map_err(|e| e.to_string())?;
+
+    Ok(Some(fingerprint(&raw)))
+}
+
+/// Checks an Ed25519 signature made by someone else, e.g. a client signing its client info.
+pub fn verify(public_key: &str, message: &[u8], signature: &str) -> bool {
+    let public_key = base64::decode::<Vec<u8>>(public_key).ok()
+        .and_then(|raw| PKey::public_key_from_raw_bytes(&raw, Id::ED25519).ok());
+    let signature = base64::decode::<Vec<u8>>(signature).ok();
+
+    match (public_key, signature) {
+        (Some(public_key), Some(signature)) => {
+            Verifier::new_without_digest(&public_key)
+                .and_then(|mut v| v.verify_oneshot(&signature, message))
+                .unwrap_or(false)
+        },
+        _ => false
+    }
+}
@@ -0,0 +1,494 @@
+use std::{fs::{self, File}, path::PathBuf, sync::Mutex, time::{SystemTime, UNIX_EPOCH}, io::Write};
+
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use paris::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use mcsync_proto::sync::{FileHash, Manifest};
+
+use crate::manifest;
+
+lazy_static! {
+    /// Only one generation may be promoted at a time.
+    static ref COMMIT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Describes an upload that is in progress. It is stored next to the uploaded files so every
+/// HTTP worker (and a restarted backend) knows which files still have to arrive.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PendingSync {
+    /// Generation the client based its changes on.
+    pub(crate) base_generation: u64,
+
+    /// All files of the new generation as reported by the client.
+    pub(crate) files: Vec<FileHash>,
+
+    /// Files the client has to transfer (new & modified).
+    pub(crate) expected: Vec<FileHash>
+}
+
+#[derive(Debug)]
+pub enum TransferError {
+    /// There is no upload in progress or the file ID is not part of it.
+    UnknownFile,
+
+    /// Size or SHA-256 hash don't match the negotiated ones.
+    Mismatch,
+
+    /// Someone else committed a newer generation in the meantime.
+    Outdated,
+
+    Io(String)
+}
+
+pub enum Progress {
+    /// Amount of files that still have to arrive.
+    Remaining(usize),
+
+    /// All files arrived and this generation is live now.
+    Committed(u64)
+}
+
+/// Uploads land in `MINECRAFT_SAVES/<sync>/staging` first. Only once all files of a delta arrived,
+/// a new generation is assembled and the `current` symlink is swapped atomically.
+/// Anything that crashes before this swap leaves the live copy untouched.
+pub struct Staging {
+    sync_id: String
+}
+
+impl Staging {
+    pub fn new(sync_id: &str) -> Self {
+        Self { sync_id: sync_id.to_string() }
+    }
+
+    fn dir(&self) -> PathBuf {
+        manifest::sync_dir(&self.sync_id).join("staging")
+    }
+
+    fn files_dir(&self) -> PathBuf {
+        self.dir().join("files")
+    }
+
+    fn generations_dir(&self) -> PathBuf {
+        manifest::sync_dir(&self.sync_id).join("generations")
+    }
+
+    /// Left behind by a commit, so transfers that finished at the same time learn about it.
+    fn committed_file(&self) -> PathBuf {
+        self.dir().join("committed")
+    }
+
+    /// Starts a new upload. Any half-finished upload of this game server gets discarded.
+    pub fn begin(&self, pending: &PendingSync) -> Option<()> {
+        let dir = self.dir();
+
+        if dir.join("pending.json").exists() {
+            warn!("Discard unfinished upload of {}", self.sync_id);
+        }
+
+        if dir.exists() {
+            if let Err(error) = fs::remove_dir_all(&dir) {
+                error!("Couldn't remove old staging directory {}: {}", dir.display(), error);
+                return None;
+            }
+        }
+
+        if let Err(error) = fs::create_dir_all(self.files_dir()) {
+            error!("Couldn't create staging directory {}: {}", dir.display(), error);
+            return None;
+        }
+
+        let json = match serde_json::to_string(pending) {
+            Ok(j) => j,
+            Err(error) => {
+                error!("Couldn't create JSON string: {}", error);
+                return None;
+            }
+        };
+
+        match File::create(dir.join("pending.json")) {
+            Ok(mut file) => {
+                match file.write_all(json.as_bytes()).and_then(|_| file.sync_all()) {
+                    Ok(_) => Some(()),
+                    Err(error) => {
+                        error!("Couldn't write pending upload of {}: {}", self.sync_id, error);
+                        None
+                    }
+                }
+            },
+            Err(error) => {
+                error!("Couldn't create pending upload of {}: {}", self.sync_id, error);
+                None
+            }
+        }
+    }
+
+    pub fn pending(&self) -> Option<PendingSync> {
+        let file = File::open(self.dir().join("pending.json")).ok()?;
+
+        match serde_json::from_reader(file) {
+            Ok(p) => Some(p),
+            Err(error) => {
+                error!("Pending upload of {} is corrupt: {}", self.sync_id, error);
+                None
+            }
+        }
+    }
+
+    /// Files that have been announced but not yet (successfully) transferred.
+    pub fn remaining(&self, pending: &PendingSync) -> Vec<FileHash> {
+        pending.expected.iter()
+            .filter(|f| !self.files_dir().join(&f.path).exists())
+            .cloned()
+            .collect()
+    }
+
+    /// Streams an uploaded file into the staging area. The file only becomes visible
+    /// (and counts as received) once its size and hash match the negotiated ones.
+    pub async fn receive<S, E>(&self, file_id: u32, mut payload: S) -> Result<(), TransferError>
+    where
+        S: futures_util::Stream<Item = Result<actix_web::web::Bytes, E>> + Unpin,
+        E: std::fmt::Display
+    {
+        let pending = self.pending().ok_or(TransferError::UnknownFile)?;
+        let file_hash = pending.expected.iter()
+            .find(|f| f.id == file_id)
+            .ok_or(TransferError::UnknownFile)?;
+
+        let target = self.files_dir().join(&file_hash.path);
+        let partial = target.with_file_name(format!(
+            "{}.mcsync-part",
+            target.file_name().unwrap().to_string_lossy()
+        ));
+
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| TransferError::Io(e.to_string()))?;
+        }
+
+        let mut file = tokio::fs::File::create(&partial).await.map_err(|e| TransferError::Io(e.to_string()))?;
+        let mut hasher = openssl::sha::Sha256::new();
+        let mut size: u64 = 0;
+
+        while let Some(chunk) = payload.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(error) => {
+                    let _ = tokio::fs::remove_file(&partial).await;
+                    return Err(TransferError::Io(error.to_string()));
+                }
+            };
+
+            hasher.update(&chunk);
+            size += chunk.len() as u64;
+
+            if let Err(error) = file.write_all(&chunk).await {
+                let _ = tokio::fs::remove_file(&partial).await;
+                return Err(TransferError::Io(error.to_string()));
+            }
+        }
+
+        file.sync_all().await.map_err(|e| TransferError::Io(e.to_string()))?;
+
+        let hash: String = hasher.finish().iter().map(|b| format!("{:02x}", b)).collect();
+
+        if size != file_hash.size || hash != file_hash.hash {
+            warn!("Received {} for {} but it doesn't match (size {} vs {}, hash {} vs {})",
+                file_hash.path, self.sync_id, size, file_hash.size, hash, file_hash.hash);
+
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(TransferError::Mismatch);
+        }
+
+        tokio::fs::rename(&partial, &target).await.map_err(|e| TransferError::Io(e.to_string()))
+    }
+
+    /// Commits the upload once all files arrived. Transfers that finish at the same time all end up here:
+    /// The first one commits, the others get the generation it committed.
+    ///
+    /// Blocks while another commit is running, so call it from a blocking thread.
+    pub fn finish(&self) -> Result<Progress, TransferError> {
+        let _lock = COMMIT_LOCK.lock().unwrap();
+
+        let pending = match self.pending() {
+            Some(p) => p,
+            None => {
+                return fs::read_to_string(self.committed_file()).ok()
+                    .and_then(|g| g.trim().parse().ok())
+                    .map(Progress::Committed)
+                    .ok_or(TransferError::UnknownFile);
+            }
+        };
+
+        let remaining = self.remaining(&pending).len();
+        if remaining > 0 {
+            return Ok(Progress::Remaining(remaining));
+        }
+
+        self.commit(&pending).map(Progress::Committed)
+    }
+
+    /// Assembles a new generation from the staged files and the unchanged files of the current
+    /// generation, then swaps it live. Returns the new generation.
+    ///
+    /// Staged files stay until the swap succeeded, so a failed commit can be retried. Requires COMMIT_LOCK.
+    fn commit(&self, pending: &PendingSync) -> Result<u64, TransferError> {
+        let current = manifest::load(&self.sync_id).ok_or_else(|| TransferError::Io(String::from("Corrupt manifest")))?;
+
+        if current.generation != pending.base_generation {
+            let _ = fs::remove_dir_all(self.dir());
+            return Err(TransferError::Outdated);
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let generation = now.max(current.generation + 1);
+
+        let generation_dir = self.generations_dir().join(generation.to_string());
+        let generation_files = generation_dir.join("files");
+        let current_files = manifest::current_dir(&self.sync_id).join("files");
+
+        let io = |e: std::io::Error| TransferError::Io(e.to_string());
+
+        // Leftovers of a failed attempt.
+        let _ = fs::remove_dir_all(&generation_dir);
+        fs::create_dir_all(&generation_files).map_err(io)?;
+
+        for file in &pending.files {
+            let target = generation_files.join(&file.path);
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(io)?;
+            }
+
+            // Unchanged files come from the current generation. Hard links are cheap and the sources get deleted anyway.
+            let source = match pending.expected.iter().any(|f| f.path == file.path) {
+                true => self.files_dir().join(&file.path),
+                false => current_files.join(&file.path)
+            };
+
+            // Copying onto an existing target would write through the hard link into its source.
+            match fs::hard_link(&source, &target) {
+                Ok(_) => (),
+                Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                    return Err(TransferError::Io(format!("{} is part of the upload more than once", file.path)));
+                },
+                Err(_) => {
+                    fs::copy(&source, &target).map_err(io)?;
+                }
+            }
+        }
+
+        let manifest = Manifest {
+            generation,
+            files: pending.files.clone()
+        };
+
+        manifest::store(&manifest, &generation_dir).ok_or_else(|| TransferError::Io(String::from("Cannot store manifest")))?;
+
+        // The actual commit: rename() replaces the symlink atomically.
+        let link = manifest::sync_dir(&self.sync_id).join("current.new");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(PathBuf::from("generations").join(generation.to_string()), &link).map_err(io)?;
+        fs::rename(&link, manifest::current_dir(&self.sync_id)).map_err(io)?;
+
+        info!("Committed generation {} of {} ({} files)", generation, self.sync_id, manifest.files.len());
+
+        let _ = fs::remove_dir_all(self.dir());
+        if fs::create_dir_all(self.dir()).and_then(|_| fs::write(self.committed_file(), generation.to_string())).is_err() {
+            warn!("Couldn't remember generation {} of {}. Concurrent transfers may be told their upload is gone.", generation, self.sync_id);
+        }
+
+        self.remove_old_generations(generation);
+
+        Ok(generation)
+    }
+
+    /// Old generations are only kept until the next one went live. Leftovers of crashed commits are removed as well.
+    fn remove_old_generations(&self, keep: u64) {
+        let entries = match fs::read_dir(self.generations_dir()) {
+            Ok(e) => e,
+            Err(_) => return
+        };
+
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy() == keep.to_string() {
+                continue;
+            }
+
+            if let Err(error) = fs::remove_dir_all(entry.path()) {
+                warn!("Couldn't remove old generation {}: {}", entry.path().display(), error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use actix_web::web::Bytes;
+    use futures_util::stream;
+
+    use super::*;
+
+    static SAVES: Once = Once::new();
+
+    /// Every test gets its own game server inside a shared MINECRAFT_SAVES.
+    fn staging() -> Staging {
+        SAVES.call_once(|| {
+            let dir = std::env::temp_dir().join(format!("mcsync-saves-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            std::env::set_var("MINECRAFT_SAVES", dir);
+        });
+
+        let id = uuid::Uuid::new_v4().to_string();
+        fs::create_dir_all(manifest::sync_dir(&id)).unwrap();
+
+        Staging::new(&id)
+    }
+
+    fn file(id: u32, path: &str, contents: &str) -> FileHash {
+        FileHash {
+            id,
+            size: contents.len() as u64,
+            path: path.to_string(),
+            hash: openssl::sha::sha256(contents.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+        }
+    }
+
+    async fn upload(staging: &Staging, file: &FileHash, contents: &str) -> Result<(), TransferError> {
+        let payload = stream::iter(vec![Ok::<_, std::io::Error>(Bytes::from(contents.to_string()))]);
+        staging.receive(file.id, payload).await
+    }
+
+    fn live(staging: &Staging, path: &str) -> String {
+        fs::read_to_string(manifest::current_dir(&staging.sync_id).join("files").join(path)).unwrap()
+    }
+
+    fn committed(progress: Result<Progress, TransferError>) -> u64 {
+        match progress {
+            Ok(Progress::Committed(generation)) => generation,
+            Ok(Progress::Remaining(remaining)) => panic!("{} files remaining", remaining),
+            Err(error) => panic!("{:?}", error)
+        }
+    }
+
+    /// Syncs the given files as the first generation.
+    async fn first_generation(staging: &Staging, files: &[(&FileHash, &str)]) -> u64 {
+        let hashes: Vec<FileHash> = files.iter().map(|(f, _)| (*f).clone()).collect();
+        staging.begin(&PendingSync { base_generation: 0, files: hashes.clone(), expected: hashes }).unwrap();
+
+        for (file, contents) in files {
+            upload(staging, file, contents).await.unwrap();
+        }
+
+        committed(staging.finish())
+    }
+
+    #[tokio::test]
+    async fn commits_once_all_files_arrived() {
+        let staging = staging();
+        let level = file(1, "world/level.dat", "level");
+        let props = file(2, "server.properties", "motd=hi");
+
+        staging.begin(&PendingSync { base_generation: 0, files: vec![level.clone(), props.clone()], expected: vec![level.clone(), props.clone()] }).unwrap();
+
+        upload(&staging, &level, "level").await.unwrap();
+        assert!(matches!(staging.finish(), Ok(Progress::Remaining(1))));
+        assert!(!manifest::current_dir(&staging.sync_id).exists());
+
+        upload(&staging, &props, "motd=hi").await.unwrap();
+        let generation = committed(staging.finish());
+
+        assert_eq!(manifest::load(&staging.sync_id).unwrap().generation, generation);
+        assert_eq!(live(&staging, "world/level.dat"), "level");
+
+        // A transfer that finished at the same time learns about the commit instead of getting an error.
+        assert_eq!(committed(staging.finish()), generation);
+
+        fs::remove_dir_all(manifest::sync_dir(&staging.sync_id)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_unchanged_files() {
+        let staging = staging();
+        let level = file(1, "world/level.dat", "level");
+        let props = file(2, "server.properties", "motd=hi");
+        let base = first_generation(&staging, &[(&level, "level"), (&props, "motd=hi")]).await;
+
+        let changed = file(2, "server.properties", "motd=bye");
+        staging.begin(&PendingSync { base_generation: base, files: vec![level, changed.clone()], expected: vec![changed.clone()] }).unwrap();
+        upload(&staging, &changed, "motd=bye").await.unwrap();
+
+        assert!(committed(staging.finish()) > base);
+        assert_eq!(live(&staging, "world/level.dat"), "level");
+        assert_eq!(live(&staging, "server.properties"), "motd=bye");
+
+        fs::remove_dir_all(manifest::sync_dir(&staging.sync_id)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_files_that_dont_match() {
+        let staging = staging();
+        let level = file(1, "world/level.dat", "level");
+
+        staging.begin(&PendingSync { base_generation: 0, files: vec![level.clone()], expected: vec![level.clone()] }).unwrap();
+
+        assert!(matches!(upload(&staging, &level, "broken").await, Err(TransferError::Mismatch)));
+        assert!(matches!(upload(&staging, &file(7, "other", "other"), "other").await, Err(TransferError::UnknownFile)));
+        assert!(matches!(staging.finish(), Ok(Progress::Remaining(1))));
+
+        fs::remove_dir_all(manifest::sync_dir(&staging.sync_id)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_outdated_uploads() {
+        let staging = staging();
+        let level = file(1, "world/level.dat", "level");
+        let generation = first_generation(&staging, &[(&level, "level")]).await;
+
+        // Based on the state before someone else committed.
+        staging.begin(&PendingSync { base_generation: 0, files: vec![], expected: vec![] }).unwrap();
+
+        assert!(matches!(staging.finish(), Err(TransferError::Outdated)));
+        assert_eq!(manifest::load(&staging.sync_id).unwrap().generation, generation);
+        assert_eq!(live(&staging, "world/level.dat"), "level");
+
+        fs::remove_dir_all(manifest::sync_dir(&staging.sync_id)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_commits_can_be_retried() {
+        let staging = staging();
+        let level = file(1, "world/level.dat", "level");
+
+        staging.begin(&PendingSync { base_generation: 0, files: vec![level.clone()], expected: vec![level.clone()] }).unwrap();
+        upload(&staging, &level, "level").await.unwrap();
+
+        // Generations cannot be created while there is a file in the way.
+        fs::write(staging.generations_dir(), "").unwrap();
+        assert!(matches!(staging.finish(), Err(TransferError::Io(_))));
+        assert!(!manifest::current_dir(&staging.sync_id).exists());
+
+        fs::remove_file(staging.generations_dir()).unwrap();
+        committed(staging.finish());
+        assert_eq!(live(&staging, "world/level.dat"), "level");
+
+        fs::remove_dir_all(manifest::sync_dir(&staging.sync_id)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn duplicate_paths_dont_corrupt_files() {
+        let staging = staging();
+        let level = file(1, "world/level.dat", "level");
+
+        staging.begin(&PendingSync { base_generation: 0, files: vec![level.clone(), level.clone()], expected: vec![level.clone()] }).unwrap();
+        upload(&staging, &level, "level").await.unwrap();
+
+        assert!(matches!(staging.finish(), Err(TransferError::Io(_))));
+        assert_eq!(fs::read_to_string(staging.files_dir().join("world/level.dat")).unwrap(), "level");
+
+        fs::remove_dir_all(manifest::sync_dir(&staging.sync_id)).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+use std::{fs, io, path::Path, time::SystemTime};
+
+use serde_json::Value;
+
+use crate::{database::DatabaseFormat, utils::file};
+
+use super::{migration, Storage, StoreError};
+
+/// Everything in a single .json file. Easy to read and to back up, and the default.
+pub struct JsonStorage {
+    path: String,
+
+    /// Hash of the contents we read or wrote last. Tells us whether another process changed the file.
+    fingerprint: String,
+
+    /// Modification time of the file when we read or wrote it last. As long as it stays, the file isn't read again.
+    modified: Option<SystemTime>
+}
+
+impl JsonStorage {
+    pub fn new(path: String) -> Self {
+        Self { path, fingerprint: String::new(), modified: None }
+    }
+
+    /// Returns `None` if there is no file (yet).
+    fn modified(&self) -> Result<Option<SystemTime>, String> {
+        match fs::metadata(&self.path) {
+            Ok(metadata) => Ok(metadata.modified().ok()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(format!("Couldn't read database file: {}", error))
+        }
+    }
+}
+
+impl Storage for JsonStorage {
+    fn load(&mut self) -> Result<Option<DatabaseFormat>, String> {
+        let path = Path::new(&self.path);
+        self.modified = self.modified()?;
+
+        let (value, contents) = match file::read_json::<Value>(path)? {
+            Some(read) => read,
+            None => return Ok(None)
+        };
+
+        self.fingerprint = sha256::digest(&contents);
+
+        let (data, from) = migration::upgrade(value)?;
+        if let Some(version) = from {
+            let backup = file::keep_before_migration(path, version)
+                .map_err(|e| format!("Couldn't keep a copy of {} before upgrading it: {}", self.path, e))?;
+
+            // Otherwise it only hits the disk with the next change.
+            self.store(&data).map_err(|e| e.to_string())?;
+            migration::report(&self.path, version, &backup);
+        }
+
+        Ok(Some(data))
+    }
+
+    fn load_if_changed(&mut self) -> Result<Option<DatabaseFormat>, String> {
+        // Taken before reading. If the file changes in between, we just read it once more next time.
+        let modified = match self.modified()? {
+            Some(modified) if Some(modified) != self.modified => modified,
+            _ => return Ok(None)
+        };
+
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|e| format!("Couldn't read database file: {}", e))?;
+
+        let fingerprint = sha256::digest(&contents);
+        if contents.is_empty() {
+            return Ok(None);
+        }
+
+        if fingerprint == self.fingerprint {
+            self.modified = Some(modified);
+            return Ok(None);
+        }
+
+        let value = serde_json::from_str::<Value>(&contents)
+            .map_err(|e| format!("Database file changed but couldn't be parsed: {}", e))?;
+        let (data, _) = migration::upgrade(value)
+            .map_err(|e| format!("Database file changed but couldn't be read: {}", e))?;
+        self.fingerprint = fingerprint;
+        self.modified = Some(modified);
+
+        Ok(Some(data))
+    }
+
+    fn store(&mut self, data: &DatabaseFormat) -> Result<(), StoreError> {
+        let pretty = serde_json::to_string_pretty(data)
+            .map_err(|e| format!("Couldn't create JSON string: {}", e))?;
+
+        // Held until the file has been replaced, so nobody can write between our check and our write.
+        let _lock = file::lock(Path::new(&self.path))
+            .map_err(|e| format!("Couldn't lock database file ({}): {}", self.path, e))?;
+
+        match fs::read_to_string(&self.path) {
+            // Empty files count as missing, like in `file::read_json`.
+            Ok(contents) if !contents.trim().is_empty() && sha256::digest(&contents) != self.fingerprint => {
+                // Even if the modification time didn't move, `load_if_changed` has to read it now.
+                self.modified = None;
+                return Err(StoreError::Changed);
+            },
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                return Err(StoreError::Failed(format!("Couldn't read database file: {}", error)));
+            },
+            _ => ()
+        }
+
+        // The database holds every key, so never write it in a way that could leave a truncated file behind.
+        file::write_atomic(Path::new(&self.path), pretty.as_bytes(), 0o600)
+            .map_err(|e| format!("Database file ({}) couldn't be written: {}", self.path, e))?;
+
+        self.fingerprint = sha256::digest(&pretty);
+        self.modified = self.modified()?;
+
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        self.path.clone()
+    }
+}
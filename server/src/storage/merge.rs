@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use crate::database::DatabaseFormat;
+
+/// Fields that identify an entry of a list, in the order they are looked for. Entries are matched by those,
+/// so changes to the rest of an entry (e.g. renaming a client) don't look like a different entry.
+///
+/// `client` and `group` identify grants, which have no other key.
+const IDENTITY_FIELDS: &[&str] = &["id", "token", "wg_public_key", "name", "client", "group"];
+
+/// Combines our changes with the ones another process stored in the meantime.
+///
+/// `base` is what both sides started from, `ours` what we have in memory and `theirs` what is stored now.
+/// Whatever only one side changed is kept. If both changed the same field, ours wins. Entries one side
+/// removed stay removed.
+pub fn merge(base: &DatabaseFormat, ours: &DatabaseFormat, theirs: &DatabaseFormat) -> Result<DatabaseFormat, String> {
+    let [base, ours, theirs] = [base, ours, theirs].map(|data| serde_json::to_value(data).unwrap_or_default());
+
+    serde_json::from_value(merge_value(&base, &ours, &theirs))
+        .map_err(|e| format!("Couldn't merge database changes: {}", e))
+}
+
+fn merge_value(base: &Value, ours: &Value, theirs: &Value) -> Value {
+    if ours == base {
+        return theirs.clone();
+    }
+
+    if theirs == base {
+        return ours.clone();
+    }
+
+    match (ours, theirs) {
+        (Value::Object(o), Value::Object(t)) => {
+            let base = base.as_object().cloned().unwrap_or_default();
+            let mut merged = Map::new();
+
+            for key in t.keys().chain(o.keys()) {
+                if merged.contains_key(key) {
+                    continue;
+                }
+
+                let [b, o, t] = [&base, o, t].map(|m| m.get(key).cloned().unwrap_or(Value::Null));
+                let value = merge_value(&b, &o, &t);
+
+                // Null means one side removed the field (e.g. an optional one) or never had it.
+                if !value.is_null() || o.is_null() != t.is_null() {
+                    merged.insert(key.clone(), value);
+                }
+            }
+
+            Value::Object(merged)
+        },
+        (Value::Array(o), Value::Array(t)) => {
+            let base = base.as_array().cloned().unwrap_or_default();
+            Value::Array(merge_entries(&base, o, t))
+        },
+        _ => ours.clone()
+    }
+}
+
+/// Keeps the order of `theirs` and appends what we added.
+fn merge_entries(base: &[Value], ours: &[Value], theirs: &[Value]) -> Vec<Value> {
+    let base: HashMap<String, &Value> = base.iter().map(|e| (identity(e), e)).collect();
+    let ours_by_id: HashMap<String, &Value> = ours.iter().map(|e| (identity(e), e)).collect();
+    let theirs_ids: Vec<String> = theirs.iter().map(identity).collect();
+
+    let mut merged = vec![];
+
+    for (id, entry) in theirs_ids.iter().zip(theirs) {
+        match (base.get(id), ours_by_id.get(id)) {
+            // We removed it.
+            (Some(_), None) => continue,
+            (b, Some(o)) => merged.push(merge_value(b.copied().unwrap_or(&Value::Null), o, entry)),
+            (None, None) => merged.push(entry.clone())
+        }
+    }
+
+    for entry in ours {
+        let id = identity(entry);
+
+        // Otherwise they removed it or it's already in there.
+        if !base.contains_key(&id) && !theirs_ids.contains(&id) {
+            merged.push(entry.clone());
+        }
+    }
+
+    merged
+}
+
+fn identity(entry: &Value) -> String {
+    match entry {
+        Value::Object(fields) => IDENTITY_FIELDS.iter()
+            .find_map(|f| fields.get(*f).map(|v| format!("{}={}", f, v)))
+            .unwrap_or_else(|| entry.to_string()),
+        _ => entry.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::storage::migration::DATABASE_VERSION;
+
+    use super::*;
+
+    fn database(clients: Value, synced: Value) -> DatabaseFormat {
+        serde_json::from_value(json!({
+            "version": DATABASE_VERSION,
+            "keys": { "wg_private": "wg", "signing_private": "signing" },
+            "client": clients,
+            "synced": synced,
+            "pending": [],
+            "invites": [],
+            "groups": []
+        })).unwrap()
+    }
+
+    fn client(name: &str, key: &str, last_seen: u64) -> Value {
+        json!({
+            "name": name, "ipv4_address": "10.0.0.2", "last_seen": last_seen,
+            "wg_public_key": key, "wg_psk": "psk", "online": false
+        })
+    }
+
+    fn names(data: &DatabaseFormat) -> Vec<&str> {
+        data.client.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_changes_of_both_sides() {
+        let base = database(json!([client("Joe", "a", 1)]), json!([]));
+        let ours = database(json!([client("Joe", "a", 5)]), json!([]));
+        let theirs = database(json!([client("Joey", "a", 1), client("Ann", "b", 1)]), json!([]));
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(names(&merged), vec!["Joey", "Ann"]);
+        assert_eq!(merged.client[0].last_seen, 5);
+    }
+
+    #[test]
+    fn removals_stick() {
+        let base = database(json!([client("Joe", "a", 1), client("Ann", "b", 1)]), json!([]));
+
+        // Someone removed Joe while we have only seen him.
+        let ours = database(json!([client("Joe", "a", 5), client("Ann", "b", 1)]), json!([]));
+        let theirs = database(json!([client("Ann", "b", 1)]), json!([]));
+        assert_eq!(names(&merge(&base, &ours, &theirs).unwrap()), vec!["Ann"]);
+
+        // We removed Ann while someone else added Bob.
+        let ours = database(json!([client("Joe", "a", 1)]), json!([]));
+        let theirs = database(json!([client("Joe", "a", 1), client("Ann", "b", 1), client("Bob", "c", 1)]), json!([]));
+        assert_eq!(names(&merge(&base, &ours, &theirs).unwrap()), vec!["Joe", "Bob"]);
+    }
+
+    #[test]
+    fn optional_fields_can_be_cleared() {
+        let sync = |lease: Value, last_hosted: u64| json!([{
+            "id": "s", "name": "world", "owner": "Joe", "default_role": "host", "grants": [],
+            "start_file": "start", "created": 0, "lease": lease, "last_host": "Joe",
+            "last_hosted": last_hosted, "last_status": null
+        }]);
+        let lease = json!({ "holder": "10.0.0.2", "acquired": 1, "heartbeat": 1 });
+
+        let base = database(json!([]), sync(lease.clone(), 1));
+        let ours = database(json!([]), sync(Value::Null, 2));
+        let theirs = database(json!([]), sync(lease, 1));
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert!(merged.synced[0].lease.is_none());
+        assert_eq!(merged.synced[0].last_hosted, 2);
+    }
+}
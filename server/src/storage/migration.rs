@@ -0,0 +1,163 @@
+use std::path::Path;
+
+use mcsync_proto::migration::{self, entries, fill, Step};
+use serde_json::{Map, Value};
+
+use crate::database::DatabaseFormat;
+
+/// Upgrades a stored database by one version each. Append new ones, never change released ones.
+const STEPS: &[Step] = &[v1_to_v2, v2_to_v3];
+
+/// Version of the database this build writes.
+pub const DATABASE_VERSION: u16 = migration::latest(STEPS);
+
+/// Brings stored data up to date. Also returns the version it had, if it had to be migrated.
+pub fn upgrade(mut value: Value) -> Result<(DatabaseFormat, Option<u16>), String> {
+    let from = migration::migrate(&mut value, STEPS)?;
+    let data = serde_json::from_value(value).map_err(|e| format!("Invalid database: {}", e))?;
+
+    Ok((data, from))
+}
+
+/// Stdout may be reserved for JSON, see `utils::file`.
+pub fn report(location: &str, from: u16, backup: &Path) {
+    eprintln!("Upgraded the database at {} from version {} to {}. The previous version is kept at {}.", location, from, DATABASE_VERSION, backup.display());
+}
+
+/// Version 1 grew by adding fields that were filled with defaults while reading. Version 2 always contains all of them.
+fn v1_to_v2(database: &mut Map<String, Value>) -> Result<(), String> {
+    let keys = database.get_mut("keys").and_then(Value::as_object_mut).ok_or_else(|| String::from("keys are missing"))?;
+
+    // Generated by `Database::new`.
+    fill(keys, "signing_private", Value::from(""));
+
+    for sync in entries(database, "synced")? {
+        fill(sync, "owner", Value::from(""));
+        fill(sync, "start_file", Value::from(""));
+        fill(sync, "created", Value::from(0));
+        fill(sync, "lease", Value::Null);
+        fill(sync, "last_host", Value::from(""));
+        fill(sync, "last_hosted", Value::from(0));
+        fill(sync, "last_status", Value::Null);
+    }
+
+    for client in entries(database, "client")? {
+        fill(client, "online", Value::from(false));
+    }
+
+    for pending in entries(database, "pending")? {
+        fill(pending, "fingerprint", Value::from(""));
+        fill(pending, "server_info", Value::Null);
+    }
+
+    fill(database, "invites", Value::Array(vec![]));
+
+    Ok(())
+}
+
+/// Roles per game server replace `share`. Shared game servers stay open to everyone, the others only to their owner.
+fn v2_to_v3(database: &mut Map<String, Value>) -> Result<(), String> {
+    fill(database, "groups", Value::Array(vec![]));
+
+    for sync in entries(database, "synced")? {
+        let shared = sync.remove("share").and_then(|s| s.as_bool()).unwrap_or(true);
+
+        fill(sync, "default_role", Value::from(if shared { "host" } else { "none" }));
+        fill(sync, "grants", Value::Array(vec![]));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::database::Role;
+
+    use super::*;
+
+    /// Databases as written by every historical version, oldest first.
+    const FIXTURES: &[(&str, &str)] = &[
+        ("v1-initial", include_str!("../../tests/fixtures/database/v1-initial.json")),
+        ("v1", include_str!("../../tests/fixtures/database/v1.json")),
+        ("v2", include_str!("../../tests/fixtures/database/v2.json")),
+        ("v3", include_str!("../../tests/fixtures/database/v3.json"))
+    ];
+
+    fn current() -> Value {
+        parse(FIXTURES.last().unwrap().1)
+    }
+
+    fn parse(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn fixtures_cover_every_version() {
+        let last = FIXTURES.iter().map(|(_, json)| parse(json)["version"].as_u64().unwrap()).max();
+        assert_eq!(last, Some(DATABASE_VERSION as u64));
+    }
+
+    #[test]
+    fn upgrades_every_version() {
+        for (name, json) in FIXTURES {
+            let (data, from) = upgrade(parse(json)).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(data.version, DATABASE_VERSION, "{}", name);
+            assert_eq!(from.is_some(), *name != "v3", "{}", name);
+            assert_eq!(data.client[0].name, "Joe Doe", "{}", name);
+            assert_eq!(data.synced[0].id, "4c3b5ba8-2a43-4b6b-9b70-3c1e1fe0b0d5", "{}", name);
+        }
+    }
+
+    #[test]
+    fn keeps_existing_fields() {
+        let (data, _) = upgrade(parse(FIXTURES[1].1)).unwrap();
+        assert_eq!(data.synced[0].owner, "Joe Doe");
+        assert_eq!(data.synced[0].start_file, "start");
+        assert_eq!(data.pending[0].fingerprint, "");
+        assert_eq!(data.invites.len(), 1);
+    }
+
+    #[test]
+    fn fills_missing_fields() {
+        let (data, _) = upgrade(parse(FIXTURES[0].1)).unwrap();
+        assert_eq!(serde_json::to_value(&data).unwrap()["keys"]["signing_private"], "");
+        assert_eq!(data.synced[0].owner, "");
+        assert!(data.synced[0].lease.is_none());
+        assert!(!data.client[0].online);
+        assert!(data.pending.is_empty());
+        assert!(data.invites.is_empty());
+        assert!(data.groups.is_empty());
+    }
+
+    #[test]
+    fn keeps_game_servers_open() {
+        let (data, _) = upgrade(parse(FIXTURES[2].1)).unwrap();
+        assert_eq!(data.synced[0].default_role, Role::Host);
+        assert!(data.synced[0].grants.is_empty());
+
+        let mut private = parse(FIXTURES[2].1);
+        private["synced"][0]["share"] = json!(false);
+        let (data, _) = upgrade(private).unwrap();
+        assert_eq!(data.synced[0].default_role, Role::None);
+    }
+
+    #[test]
+    fn current_version_stays_untouched() {
+        let mut value = current();
+        assert_eq!(migration::migrate(&mut value, STEPS), Ok(None));
+        assert_eq!(value, current());
+
+        // What we write has to read back without changes.
+        let (data, _) = upgrade(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&data).unwrap(), value);
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        let mut value = current();
+        value["version"] = json!(DATABASE_VERSION + 1);
+        assert!(upgrade(value).is_err());
+    }
+}
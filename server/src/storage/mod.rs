@@ -0,0 +1,131 @@
+use std::{fmt, process::exit};
+
+use paris::error;
+
+use crate::{database::DatabaseFormat, env};
+
+pub mod json;
+pub mod merge;
+pub mod migration;
+pub mod sqlite;
+
+/// Set using DATABASE_BACKEND.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Json,
+    Sqlite
+}
+
+/// Where `Database` keeps its data. The database works in-memory and hands everything over on flush.
+///
+/// The backend and CLI commands run in separate processes, so a storage has to tell whether someone else
+/// changed the stored data in the meantime.
+pub trait Storage: Send {
+    /// Returns `None` if nothing has been stored yet.
+    fn load(&mut self) -> Result<Option<DatabaseFormat>, String>;
+
+    /// Only returns something if another process changed the stored data since we loaded or stored it last.
+    /// Called on every request, so it has to tell cheaply that nothing changed.
+    fn load_if_changed(&mut self) -> Result<Option<DatabaseFormat>, String>;
+
+    /// Refuses to overwrite what another process stored since we loaded or stored it last.
+    fn store(&mut self, data: &DatabaseFormat) -> Result<(), StoreError>;
+
+    /// Where the data lives, for messages.
+    fn location(&self) -> String;
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    /// Someone else stored something in the meantime. Pick it up using `Storage::load_if_changed` and try again.
+    Changed,
+    Failed(String)
+}
+
+impl From<String> for StoreError {
+    fn from(error: String) -> Self {
+        StoreError::Failed(error)
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Changed => f.write_str("Another process changed the database in the meantime"),
+            StoreError::Failed(error) => f.write_str(error)
+        }
+    }
+}
+
+/// Opens the storage configured by DATABASE_BACKEND and DATABASE_PATH.
+pub fn open() -> Box<dyn Storage> {
+    let path = env::get_database_path();
+
+    match env::get_database_backend() {
+        Backend::Json => Box::new(json::JsonStorage::new(path)),
+        Backend::Sqlite => match sqlite::SqliteStorage::open(&path) {
+            Ok(storage) => Box::new(storage),
+            Err(error) => {
+                error!("Couldn't open SQLite database at {}: {}", path, error);
+                exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{json::JsonStorage, sqlite::SqliteStorage, *};
+
+    fn fixture() -> DatabaseFormat {
+        let value = serde_json::from_str(include_str!("../../tests/fixtures/database/v3.json")).unwrap();
+        migration::upgrade(value).unwrap().0
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcsync-storage-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    /// Like a CLI command and the backend, each with their own copy.
+    fn refuses_to_overwrite(open: impl Fn() -> Box<dyn Storage>) {
+        let (mut first, mut second) = (open(), open());
+        let mut data = fixture();
+
+        assert!(first.load().unwrap().is_none());
+        first.store(&data).unwrap();
+        assert!(second.load().unwrap().is_some());
+
+        data.client.clear();
+        first.store(&data).unwrap();
+
+        assert!(matches!(second.store(&fixture()), Err(StoreError::Changed)));
+        assert!(second.load_if_changed().unwrap().unwrap().client.is_empty());
+        second.store(&fixture()).unwrap();
+
+        assert!(first.load_if_changed().unwrap().is_some());
+        assert!(second.load_if_changed().unwrap().is_none());
+    }
+
+    #[test]
+    fn json_refuses_to_overwrite() {
+        let dir = temp_dir();
+        let path = dir.join("database.json").to_string_lossy().to_string();
+
+        refuses_to_overwrite(|| Box::new(JsonStorage::new(path.clone())));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sqlite_refuses_to_overwrite() {
+        let dir = temp_dir();
+        let path = dir.join("database.sqlite").to_string_lossy().to_string();
+
+        refuses_to_overwrite(|| Box::new(SqliteStorage::open(&path).unwrap()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use std::{path::PathBuf, time::Duration};
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use crate::database::DatabaseFormat;
+
+use super::{migration, Storage, StoreError};
+
+/// Every entry is kept as JSON next to the columns that identify it. Entries get new fields all the time,
+/// this way they don't need a migration each time.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS clients (name TEXT PRIMARY KEY, data TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS synced (id TEXT PRIMARY KEY, data TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS pending (name TEXT PRIMARY KEY, data TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS invites (token TEXT PRIMARY KEY, data TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS groups (name TEXT PRIMARY KEY, data TEXT NOT NULL);
+";
+
+/// Stores the database in SQLite. Writes are transactions, so the backend and CLI commands never see half of a change.
+pub struct SqliteStorage {
+    path: String,
+    connection: Connection,
+
+    /// Changes once another connection committed something. See `PRAGMA data_version`.
+    data_version: i64
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(|e| e.to_string())?;
+
+        // CLI commands write while the backend is running. Wait for each other instead of failing right away.
+        connection.busy_timeout(Duration::from_secs(5)).map_err(|e| e.to_string())?;
+        connection.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+        connection.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+
+        Ok(Self { path: path.to_string(), connection, data_version: 0 })
+    }
+
+    /// Everything as one JSON object, the way it'd look in database.json. Migrations work on that.
+    fn read(&mut self) -> Result<Option<Value>, String> {
+        // A transaction, so we don't read half of someone else's changes.
+        let tx = self.connection.transaction().map_err(|e| e.to_string())?;
+
+        // Taken inside the transaction, so it belongs to exactly what we read.
+        self.data_version = data_version(&tx)?;
+
+        let keys = match get_meta(&tx, "keys")? {
+            Some(k) => k,
+            None => return Ok(None)
+        };
+
+        let version = get_meta(&tx, "version")?.unwrap_or_else(|| String::from("1"));
+
+        let data = json!({
+            "version": version.parse::<u16>().map_err(|_| format!("Invalid version {}", version))?,
+            "keys": serde_json::from_str::<Value>(&keys).map_err(|e| format!("Invalid keys: {}", e))?,
+            "client": get_entries::<Value>(&tx, "clients")?,
+            "synced": get_entries::<Value>(&tx, "synced")?,
+            "pending": get_entries::<Value>(&tx, "pending")?,
+            "invites": get_entries::<Value>(&tx, "invites")?,
+            "groups": get_entries::<Value>(&tx, "groups")?
+        });
+
+        tx.commit().map_err(|e| e.to_string())?;
+
+        Ok(Some(data))
+    }
+
+    fn write(&mut self, data: &DatabaseFormat) -> Result<(), StoreError> {
+        // Takes the write lock right away, so nobody can commit between our check and our commit.
+        let tx = self.connection.transaction_with_behavior(TransactionBehavior::Immediate).map_err(|e| e.to_string())?;
+
+        // Our own commits don't change it.
+        if data_version(&tx)? != self.data_version {
+            return Err(StoreError::Changed);
+        }
+
+        set_meta(&tx, "version", &data.version.to_string())?;
+        set_meta(&tx, "keys", &to_json(&data.keys)?)?;
+        set_entries(&tx, "clients", data.client.iter().map(|c| (c.name.as_str(), c)))?;
+        set_entries(&tx, "synced", data.synced.iter().map(|s| (s.id.as_str(), s)))?;
+        set_entries(&tx, "pending", data.pending.iter().map(|p| (p.name.as_str(), p)))?;
+        set_entries(&tx, "invites", data.invites.iter().map(|i| (i.token.as_str(), i)))?;
+        set_entries(&tx, "groups", data.groups.iter().map(|g| (g.name.as_str(), g)))?;
+
+        tx.commit().map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// A consistent copy of the whole database, even while someone else is writing.
+    fn keep_before_migration(&self, version: u16) -> Result<PathBuf, String> {
+        let backup = PathBuf::from(format!("{}.v{}.bak", self.path, version));
+        if !backup.exists() {
+            self.connection.execute("VACUUM INTO ?1", params![backup.to_string_lossy()]).map_err(|e| e.to_string())?;
+        }
+
+        Ok(backup)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&mut self) -> Result<Option<DatabaseFormat>, String> {
+        let value = match self.read()? {
+            Some(value) => value,
+            None => return Ok(None)
+        };
+
+        let (data, from) = migration::upgrade(value)?;
+        if let Some(version) = from {
+            let backup = self.keep_before_migration(version)
+                .map_err(|e| format!("Couldn't keep a copy of {} before upgrading it: {}", self.path, e))?;
+
+            self.store(&data).map_err(|e| e.to_string())?;
+            migration::report(&self.path, version, &backup);
+        }
+
+        Ok(Some(data))
+    }
+
+    fn load_if_changed(&mut self) -> Result<Option<DatabaseFormat>, String> {
+        if data_version(&self.connection)? == self.data_version {
+            return Ok(None);
+        }
+
+        match self.read()? {
+            Some(value) => Ok(Some(migration::upgrade(value)?.0)),
+            None => Ok(None)
+        }
+    }
+
+    fn store(&mut self, data: &DatabaseFormat) -> Result<(), StoreError> {
+        self.write(data).map_err(|e| match e {
+            StoreError::Failed(error) => StoreError::Failed(format!("Couldn't write to SQLite database at {}: {}", self.path, error)),
+            changed => changed
+        })
+    }
+
+    fn location(&self) -> String {
+        self.path.clone()
+    }
+}
+
+/// Changes once another connection committed something.
+fn data_version(connection: &Connection) -> Result<i64, String> {
+    connection.query_row("PRAGMA data_version", [], |row| row.get(0)).map_err(|e| e.to_string())
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("Couldn't create JSON string: {}", e))
+}
+
+fn get_meta(tx: &Transaction, key: &str) -> Result<Option<String>, String> {
+    tx.query_row("SELECT value FROM meta WHERE key = ?1", params![key], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+fn set_meta(tx: &Transaction, key: &str, value: &str) -> Result<(), String> {
+    tx.execute("INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)", params![key, value])
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Entries keep the order they have been added in.
+fn get_entries<T: DeserializeOwned>(tx: &Transaction, table: &str) -> Result<Vec<T>, String> {
+    let mut statement = tx.prepare(&format!("SELECT data FROM {} ORDER BY rowid", table)).map_err(|e| e.to_string())?;
+    let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(|e| e.to_string())?;
+
+    rows
+        .map(|row| {
+            let json = row.map_err(|e| e.to_string())?;
+            serde_json::from_str(&json).map_err(|e| format!("Invalid entry in {}: {}", table, e))
+        })
+        .collect()
+}
+
+/// Replaces all entries of a table. The database is tiny, so there is no point in tracking single changes.
+fn set_entries<'a, T: Serialize + 'a>(tx: &Transaction, table: &str, entries: impl Iterator<Item = (&'a str, &'a T)>) -> Result<(), String> {
+    tx.execute(&format!("DELETE FROM {}", table), []).map_err(|e| e.to_string())?;
+
+    // The first column is the primary key of every table.
+    let mut statement = tx.prepare(&format!("INSERT INTO {} VALUES (?1, ?2)", table)).map_err(|e| e.to_string())?;
+    for (key, entry) in entries {
+        statement.execute(params![key, to_json(entry)?])
+            .map_err(|e| format!("Couldn't store {} in {}: {}", key, table, e))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::unix::fs::OpenOptionsExt,
+    path::Path
+};
+
+use mcsync_proto::file::with_suffix;
+
+pub use mcsync_proto::file::{keep_before_migration, read_json, write_atomic};
+
+/// Keeps other processes from writing `path` until the returned file is dropped. Only works if they lock it as well.
+pub fn lock(path: &Path) -> io::Result<File> {
+    let file = OpenOptions::new().write(true).create(true).truncate(false).mode(0o600).open(with_suffix(path, "lock"))?;
+    file.lock()?;
+
+    Ok(file)
+}
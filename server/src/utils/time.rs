@@ -0,0 +1,24 @@
+/// Parses durations like "30m", "24h" or "7d" into seconds.
+pub fn parse_duration(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+
+    let amount: u64 = amount.parse()
+        .map_err(|_| format!("\"{}\" doesn't start with a number", value))?;
+
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" | "" => 3600,
+        "d" => 86400,
+        "w" => 604800,
+        _ => return Err(format!("Unknown unit \"{}\". Use s, m, h, d or w", unit))
+    };
+
+    match amount.checked_mul(factor) {
+        Some(0) => Err(String::from("Duration has to be longer than zero")),
+        Some(seconds) => Ok(seconds),
+        None => Err(String::from("Duration is too long"))
+    }
+}
@@ -1,20 +1,34 @@
-use std::{path::Path, process::exit, fs::{OpenOptions, File}, io::Write};
+use std::{fs::{OpenOptions, File}, io::{Read, Write}, os::unix::net::UnixStream, time::Duration};
 
-use ipnet::Ipv4Net;
-use paris::{error, info};
+use domain::utils::base64;
+use rand::RngCore;
+use paris::error;
 
-use crate::{database::Database, env};
+use crate::{database::Database, docker::DockerManager, env};
+
+/// What the running interface knows about a peer.
+#[derive(Default, Debug)]
+pub struct PeerStats {
+    /// Base64, just like in the database.
+    pub(crate) public_key: String,
+
+    /// Unix timestamp or 0 if there was no handshake yet.
+    pub(crate) last_handshake: u64,
+    pub(crate) rx_bytes: u64,
+    pub(crate) tx_bytes: u64
+}
 
 pub struct Wireguard {
-    database: Database,
     private_key: wireguard_keys::Privkey,
     endpoint: String,
     config_file: String,
+    socket: String
 }
 
 impl Wireguard {
-    pub fn new(database: Database, endpoint: String) -> Self {
+    pub fn new(database: &Database, endpoint: String) -> Self {
         let config_file = env::get_wg_config();
+        let socket = env::get_wg_socket();
 
         let key = database.get_wireguard_private_key();
 
@@ -22,16 +36,17 @@ impl Wireguard {
             private_key: key,
             endpoint,
             config_file,
-            database,
+            socket
         }
     }
 
-    pub fn regenerate_config(&self) {
+    /// Rewrites the whole config file from `database`, so it survives restarts of the WireGuard container.
+    pub fn regenerate_config(&self, database: &Database) {
         match File::create(&self.config_file) {
             Ok(mut file) => {
                 let _ = file.write(self.generate_server_config().as_bytes());
                 
-                for client in self.database.get_clients() {
+                for client in database.get_clients() {
                     self.add_peer(client.wg_public_key, client.wg_psk, client.ipv4_address);
                 }
             },
@@ -93,4 +108,154 @@ AllowedIPs = {}/32
             }
         };
     }
+
+    /// 32 random bytes, base64 encoded like WireGuard does.
+    pub fn generate_psk() -> String {
+        let mut psk: [u8; 32] = [0; 32];
+        rand::thread_rng().fill_bytes(&mut psk);
+
+        base64::encode_string(&psk)
+    }
+
+    /// Last resort if a change cannot be applied at runtime. This disconnects everyone for a moment.
+    pub async fn restart(&self, docker: &DockerManager) -> Option<()> {
+        match docker.get_vpn_container().await {
+            Some(vpn) => {
+                if let Err(error) = docker.restart_container(vpn).await {
+                    error!("Couldn't restart WireGuard container: {}", error);
+                    return None;
+                }
+
+                Some(())
+            },
+            None => {
+                error!("Cannot find WireGuard container. Is it stopped?");
+                None
+            }
+        }
+    }
+
+    /// Adds (or updates) a peer on the running interface, so nobody gets disconnected.
+    ///
+    /// See https://www.wireguard.com/xplatform/#configuration-protocol
+    pub fn apply_peer(&self, pub_key: &str, psk_key: &str, ip: &str) -> Option<()> {
+        let public_key = Self::to_hex(pub_key)?;
+        let psk = match wireguard_keys::Secret::from_base64(psk_key) {
+            Ok(k) => k.to_hex(),
+            Err(error) => {
+                error!("Preshared key of {} is invalid: {}", ip, error);
+                return None;
+            }
+        };
+
+        self.uapi_set(&format!(
+            "public_key={}\npreshared_key={}\nreplace_allowed_ips=true\nallowed_ip={}/32\n",
+            public_key, psk, ip
+        ))
+    }
+
+    /// Removes a peer from the running interface. Its tunnel stops working immediately.
+    pub fn remove_live_peer(&self, pub_key: &str) -> Option<()> {
+        let public_key = Self::to_hex(pub_key)?;
+
+        self.uapi_set(&format!("public_key={}\nremove=true\n", public_key))
+    }
+
+    fn to_hex(pub_key: &str) -> Option<String> {
+        match wireguard_keys::Pubkey::from_base64(pub_key) {
+            Ok(k) => Some(k.to_hex()),
+            Err(error) => {
+                error!("WireGuard public key {} is invalid: {}", pub_key, error);
+                None
+            }
+        }
+    }
+
+    /// Returns the state of all peers on the running interface.
+    pub fn peers(&self) -> Result<Vec<PeerStats>, String> {
+        let response = self.uapi("get=1\n\n")?;
+        let mut peers: Vec<PeerStats> = vec![];
+
+        for line in response.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some(pair) => pair,
+                None => continue
+            };
+
+            // Every peer starts with its public key. Anything before belongs to the interface.
+            if key == "public_key" {
+                match wireguard_keys::Pubkey::from_hex(value) {
+                    Ok(k) => peers.push(PeerStats { public_key: k.to_base64(), ..Default::default() }),
+                    Err(error) => {
+                        return Err(format!("WireGuard reported an invalid public key {}: {}", value, error));
+                    }
+                }
+
+                continue;
+            }
+
+            let peer = match peers.last_mut() {
+                Some(p) => p,
+                None => continue
+            };
+
+            match key {
+                "last_handshake_time_sec" => peer.last_handshake = value.parse().unwrap_or(0),
+                "rx_bytes" => peer.rx_bytes = value.parse().unwrap_or(0),
+                "tx_bytes" => peer.tx_bytes = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+
+        Ok(peers)
+    }
+
+    /// Sends a `set` operation to the userspace API socket of wireguard-go and checks its errno.
+    fn uapi_set(&self, operation: &str) -> Option<()> {
+        let response = match self.uapi(&format!("set=1\n{}\n", operation)) {
+            Ok(r) => r,
+            Err(error) => {
+                error!("{}", error);
+                return None;
+            }
+        };
+
+        match response.lines().find_map(|l| l.strip_prefix("errno=")) {
+            Some("0") => Some(()),
+            Some(errno) => {
+                error!("WireGuard rejected the change (errno {})", errno);
+                None
+            },
+            None => {
+                error!("WireGuard sent an unexpected response: {}", response);
+                None
+            }
+        }
+    }
+
+    /// Sends a raw request and reads until the empty line that terminates every response.
+    fn uapi(&self, request: &str) -> Result<String, String> {
+        let mut stream = UnixStream::connect(&self.socket)
+            .map_err(|e| format!("Cannot connect to WireGuard's API socket at {}: {}", self.socket, e))?;
+
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+
+        stream.write_all(request.as_bytes())
+            .map_err(|e| format!("Cannot send command to WireGuard: {}", e))?;
+
+        let mut response = String::new();
+        let mut buffer = [0u8; 1024];
+
+        while !response.ends_with("\n\n") {
+            match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(size) => response.push_str(&String::from_utf8_lossy(&buffer[..size])),
+                Err(error) => {
+                    return Err(format!("Cannot read response of WireGuard: {}", error));
+                }
+            }
+        }
+
+        Ok(response)
+    }
 }